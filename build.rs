@@ -1,28 +1,171 @@
 use shaderc::*;
+use std::path::{Path, PathBuf};
+
+#[path = "src/vulkan/permutations.rs"]
+mod permutations;
+use permutations::*;
+
+//  Every `src/*.{vert,frag,comp,geom,tesc,tese}` is compiled into `$OUT_DIR/<name>.spv`.
+//  `src/vulkan/shaders.rs` then embeds them with `include_bytes!`.
+//  { src/my-shader.vert -> shaders::MY_SHADER }
+//  Only the shaders found here are watched, so `touch build.rs` after adding a new one.
+const SHADER_DIR: &str = "src";
+
+//  Where `#include <...>` looks.
+//  `#include "..."` looks next to the including file first.
+const INCLUDE_DIRS: &[&str] = &["src/include"];
 
 fn main() {
-    //  (ShaderKind::Vertex, "my_shader") =>
-    //  { src/my_shader.vert -> my_shader.spv }
-    let shaders = [
-        (ShaderKind::Vertex, "vertex"),
-        (ShaderKind::Fragment, "fragment"),
-    ];
-    shaders.into_iter().for_each(|(kind, input)| {
-        let compiler = Compiler::new().unwrap();
-        let ext = match kind {
-            ShaderKind::Vertex => ".vert",
-            ShaderKind::Fragment => ".frag",
-            _ => panic!("That shader type is not supported in build.rs."),
-        };
-        let input_file = String::from("src/") + input + ext;
-        println!("cargo:rerun-if-changed={}", input_file);
-        let input_str = std::fs::read_to_string(&input_file)
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/vulkan/permutations.rs");
+    //  Cargo considers a missing path to always be dirty.
+    INCLUDE_DIRS
+        .iter()
+        .filter(|dir| Path::new(dir).is_dir())
+        .for_each(|dir| println!("cargo:rerun-if-changed={}", dir));
+
+    //  Find Shaders
+    let sources: Vec<(String, PathBuf, ShaderKind)> = std::fs::read_dir(SHADER_DIR)
+        .unwrap_or_else(|_| panic!("Could not read {}", SHADER_DIR))
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let kind = match path.extension()?.to_str()? {
+                "vert" => ShaderKind::Vertex,
+                "frag" => ShaderKind::Fragment,
+                "comp" => ShaderKind::Compute,
+                "geom" => ShaderKind::Geometry,
+                "tesc" => ShaderKind::TessControl,
+                "tese" => ShaderKind::TessEvaluation,
+                _ => None?,
+            };
+            Some((path.file_stem()?.to_str()?.to_owned(), path, kind))
+        })
+        .collect();
+    sources
+        .iter()
+        .for_each(|(_, path, _)| println!("cargo:rerun-if-changed={}", path.display()));
+
+    //  Every shader once as is, then every permutation
+    let mut jobs: Vec<(String, &Path, ShaderKind, Defines)> = sources
+        .iter()
+        .map(|(name, path, kind)| (name.clone(), path.as_path(), *kind, &[][..]))
+        .collect();
+    for (input, output, defines) in PERMUTATIONS {
+        let (_, path, kind) = sources
+            .iter()
+            .find(|(name, _, _)| name == input)
+            .unwrap_or_else(|| panic!("Permutation {} refers to unknown shader {}", output, input));
+        jobs.push((output.to_string(), path.as_path(), *kind, *defines));
+    }
+
+    //  Each output turns into a `pub const`, so they can't clash.
+    jobs.sort_by_cached_key(|(output, _, _, _)| const_name(output));
+    if let Some(pair) = jobs
+        .windows(2)
+        .find(|pair| const_name(&pair[0].0) == const_name(&pair[1].0))
+    {
+        panic!(
+            "{} ({}) and {} ({}) would both become shaders::{}",
+            pair[0].1.display(),
+            pair[0].0,
+            pair[1].1.display(),
+            pair[1].0,
+            const_name(&pair[0].0)
+        );
+    }
+
+    //  Compile Everything
+    //  Keep going after a failure so that all of the errors are reported at once.
+    let compiler = Compiler::new().unwrap();
+    let mut generated = String::from("//  Generated by build.rs.\n");
+    let mut failures = 0;
+    for (output, path, kind, defines) in jobs {
+        let input_file = path.to_string_lossy();
+        let input_str = std::fs::read_to_string(path)
             .unwrap_or_else(|_| panic!("Could not read {}", input_file));
-        let binary = compiler
-            .compile_into_spirv(&input_str, kind, &input_file, "main", None)
-            .unwrap_or_else(|_| panic!("Failed to compile {}.", input_file));
-        let output_file = String::from(input) + ".spv";
-        std::fs::write(&output_file, binary.as_binary_u8())
-            .unwrap_or_else(|_| panic!("Could not write to {}", output_file));
-    });
+
+        let mut options = CompileOptions::new().unwrap();
+        options.set_include_callback(resolve_include);
+        defines
+            .iter()
+            .for_each(|(name, value)| options.add_macro_definition(name, *value));
+
+        match compiler.compile_into_spirv(&input_str, kind, &input_file, "main", Some(&options)) {
+            Ok(binary) => {
+                if binary.get_num_warnings() > 0 {
+                    binary
+                        .get_warning_messages()
+                        .lines()
+                        .for_each(|line| println!("cargo:warning={}", line));
+                }
+                let output_file = out_dir.join(String::from(&output) + ".spv");
+                std::fs::write(&output_file, binary.as_binary_u8())
+                    .unwrap_or_else(|_| panic!("Could not write to {}", output_file.display()));
+                generated += &format!(
+                    "pub const {}: &[u8] = include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{}.spv\"));\n",
+                    const_name(&output),
+                    output
+                );
+            }
+            Err(Error::CompilationError(_, messages)) => {
+                failures += 1;
+                eprintln!("\nFailed to compile {} ({}):", input_file, output);
+                messages.lines().for_each(|line| eprintln!("    {}", line));
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("\nFailed to compile {} ({}): {}", input_file, output, e);
+            }
+        }
+    }
+    if failures > 0 {
+        panic!("{} shader(s) failed to compile, see above.", failures);
+    }
+
+    let generated_file = out_dir.join("shaders.rs");
+    std::fs::write(&generated_file, generated)
+        .unwrap_or_else(|_| panic!("Could not write to {}", generated_file.display()));
+}
+
+//  `post-fx` -> `POST_FX`
+fn const_name(name: &str) -> String {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        panic!(
+            "Shader name `{}` has to start with a letter to be usable as shaders::NAME",
+            name
+        );
+    }
+    name.chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+fn resolve_include(
+    requested: &str,
+    ty: IncludeType,
+    requesting: &str,
+    _depth: usize,
+) -> IncludeCallbackResult {
+    let relative = Path::new(requesting)
+        .parent()
+        .filter(|_| ty == IncludeType::Relative)
+        .map(|dir| dir.join(requested));
+    let path = relative
+        .into_iter()
+        .chain(
+            INCLUDE_DIRS
+                .iter()
+                .map(|dir| Path::new(dir).join(requested)),
+        )
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("Could not find {} (included by {})", requested, requesting))?;
+    println!("cargo:rerun-if-changed={}", path.display());
+    Ok(ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content: std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?,
+    })
 }
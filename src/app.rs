@@ -1,6 +1,6 @@
 use super::vulkan::*;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
@@ -17,7 +17,8 @@ impl App {
 
     pub fn run(self, event_loop: EventLoop<()>) -> Option<()> {
         let dims = self.wnd.inner_size();
-        let mut playground = VulkanPlayground::create(&self.wnd, dims.width, dims.height)?;
+        let mut playground =
+            VulkanPlayground::create(&self.wnd, dims.width, dims.height, Settings::from_args())?;
         event_loop.run(move |e, _, control_flow| match e {
             Event::RedrawRequested(window_id) if window_id == self.wnd.id() => {
                 playground.render(&self.wnd);
@@ -27,9 +28,21 @@ impl App {
             }
             Event::WindowEvent { window_id, event } if window_id == self.wnd.id() => match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::Resized(size) => {
-                    playground.resize(size.width, size.height);
-                }
+                //  The playground keeps trying while rendering, so this isn't fatal
+                WindowEvent::Resized(size) => playground
+                    .resize(size.width, size.height)
+                    .unwrap_or_else(|| {
+                        println!("[App] Could not resize to {}x{}", size.width, size.height)
+                    }),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } => playground.key_pressed(key),
                 _ => {}
             },
             _ => {}
@@ -0,0 +1,72 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "scene.glsl"
+#include "lighting.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+//  Written by gbuffer.frag in the subpass before, read back for the same pixel.
+layout(input_attachment_index = 0, binding = 6) uniform subpassInput u_albedo;
+layout(input_attachment_index = 1, binding = 7) uniform subpassInput u_normal;
+layout(input_attachment_index = 2, binding = 8) uniform subpassInput u_material;
+layout(input_attachment_index = 3, binding = 9) uniform subpassInput u_depth;
+
+//  Same as `MAX_POINT_LIGHTS` in deferred.rs
+const uint MAX_POINT_LIGHTS = 64;
+//  Same as in gbuffer.frag
+const float MAX_SHININESS = 256.0;
+
+struct PointLight {
+    //  w: How far the light reaches
+    vec4 position;
+    vec4 color;
+};
+
+//  `LightData` in deferred.rs
+layout(binding = 5) uniform LightData {
+    //  Clip space back to world space, to find out where a pixel is from its depth
+    mat4 inverse_view_proj;
+    //  x: How many of `point_lights` are used
+    uvec4 count;
+    PointLight point_lights[MAX_POINT_LIGHTS];
+} u_lights;
+
+void main() {
+
+    //  Nothing was drawn here, so keep the clear color
+    float depth = subpassLoad(u_depth).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+    vec4 world_position = u_lights.inverse_view_proj * vec4(i_uv * 2.0 - 1.0, depth, 1.0);
+    world_position /= world_position.w;
+
+    vec3 albedo = subpassLoad(u_albedo).rgb;
+    vec3 normal = normalize(subpassLoad(u_normal).xyz);
+    vec4 material = subpassLoad(u_material);
+    vec2 specular = vec2(material.x, material.y * MAX_SHININESS);
+
+    vec3 color = sun_lighting(
+        albedo,
+        normal,
+        world_position.xyz,
+        u_scene.light_view_proj * world_position,
+        specular
+    );
+    vec3 view_dir = normalize(u_scene.camera_position.xyz - world_position.xyz);
+    for (uint i = 0; i < min(u_lights.count.x, MAX_POINT_LIGHTS); i++) {
+        PointLight light = u_lights.point_lights[i];
+        vec3 to_light = light.position.xyz - world_position.xyz;
+        float distance = max(length(to_light), 0.0001);
+        //  Falls off with the square of the distance, and smoothly down to nothing at the edge
+        float edge = clamp(1.0 - pow(distance / light.position.w, 4.0), 0.0, 1.0);
+        float attenuation = edge * edge / (distance * distance + 1.0);
+        color += blinn_phong(albedo, normal, to_light / distance, view_dir, specular)
+            * light.color.rgb
+            * attenuation;
+    }
+    o_color = vec4(color, 1.0);
+
+}
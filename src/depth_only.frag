@@ -0,0 +1,5 @@
+#version 450
+
+//  Nothing to write, the depth is all that's wanted.
+void main() {
+}
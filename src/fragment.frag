@@ -1,14 +1,61 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "scene.glsl"
+#include "lighting.glsl"
 
 layout(location = 0) out vec4 o_frag_color;
 layout(location = 0) in vec4 i_frag_color;
 layout(location = 1) in vec2 i_tex_coord;
+layout(location = 2) in vec3 i_world_position;
+layout(location = 3) in vec4 i_light_position;
+layout(location = 4) in vec3 i_normal;
 layout(binding = 1) uniform sampler2D u_texture;
+//  Written by pattern.comp every frame.
+layout(binding = 2) uniform sampler2D u_pattern;
+//  A cube rendered offscreen every frame.
+layout(binding = 3) uniform sampler2D u_offscreen;
+
+//  Set through `PipelineVariant::frag`.
+layout(constant_id = 0) const bool TEXTURED = true;
+layout(constant_id = 1) const bool SHOW_UV = false;
+layout(constant_id = 2) const bool PATTERN = false;
+//  Flat color, for drawing outlines around stencilled geometry.
+layout(constant_id = 3) const bool OUTLINE = false;
+layout(constant_id = 4) const bool OFFSCREEN = false;
+//  Light and shadows. Only meaningful for the scene, the light doesn't see anything else.
+layout(constant_id = 5) const bool LIT = false;
 
 void main() {
     
-    vec4 color = texture(u_texture, i_tex_coord);
+    if (OUTLINE) {
+        o_frag_color = vec4(1.0, 0.8, 0.2, 1.0);
+        return;
+    }
+    if (SHOW_UV) {
+        o_frag_color = vec4(i_tex_coord, 0.0, 1.0);
+        return;
+    }
+
+    vec4 color = vec4(1.0);
+    if (TEXTURED) {
+        if (OFFSCREEN) {
+            color = texture(u_offscreen, i_tex_coord);
+        } else if (PATTERN) {
+            color = texture(u_pattern, i_tex_coord);
+        } else {
+            color = texture(u_texture, i_tex_coord);
+        }
+    }
     o_frag_color = color * i_frag_color;
+    if (LIT) {
+        o_frag_color.rgb = sun_lighting(
+            o_frag_color.rgb,
+            normalize(i_normal),
+            i_world_position,
+            i_light_position,
+            u_scene.specular.xy
+        );
+    }
 
 }
-
@@ -0,0 +1,33 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "scene.glsl"
+
+//  Everything deferred_lighting.frag needs to light a pixel, see `Deferred`.
+layout(location = 0) out vec4 o_albedo;
+//  In world space
+layout(location = 1) out vec4 o_normal;
+//  x: Specular strength, y: Shininess / MAX_SHININESS
+layout(location = 2) out vec4 o_material;
+layout(location = 0) in vec4 i_frag_color;
+layout(location = 1) in vec2 i_tex_coord;
+layout(location = 4) in vec3 i_normal;
+layout(binding = 1) uniform sampler2D u_texture;
+
+//  Set through `PipelineVariant::frag`, same as in fragment.frag.
+layout(constant_id = 0) const bool TEXTURED = true;
+
+//  Same as in deferred_lighting.frag. The material attachment only goes up to 1.
+const float MAX_SHININESS = 256.0;
+
+void main() {
+
+    vec4 color = vec4(1.0);
+    if (TEXTURED) {
+        color = texture(u_texture, i_tex_coord);
+    }
+    o_albedo = color * i_frag_color;
+    o_normal = vec4(normalize(i_normal), 0.0);
+    o_material = vec4(u_scene.specular.x, u_scene.specular.y / MAX_SHININESS, 0.0, 0.0);
+
+}
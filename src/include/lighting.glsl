@@ -0,0 +1,46 @@
+//  Lighting shared by fragment.frag and deferred_lighting.frag, include scene.glsl first.
+//  Both of them bind the shadow map in the same place.
+
+//  Depth as seen from the light, compared against instead of sampled.
+layout(binding = 4) uniform sampler2DShadow u_shadow_map;
+
+//  How much of the light gets here, averaged over 3x3 texels to soften the edges.
+//  Each lookup is filtered by the sampler too.
+float shadow(vec4 light_position, float n_dot_l) {
+    vec3 position = light_position.xyz / light_position.w;
+    vec2 uv = position.xy * 0.5 + 0.5;
+    //  Surfaces at a grazing angle need more slack to not shadow themselves
+    float bias = max(0.005 * (1.0 - n_dot_l), 0.001);
+    vec2 texel = 1.0 / vec2(textureSize(u_shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(u_shadow_map, vec3(uv + vec2(x, y) * texel, position.z - bias));
+        }
+    }
+    return lit / 9.0;
+}
+
+//  Blinn-Phong for one light coming from `light_dir`, before its color and shadow.
+//  `specular` is x: Strength, y: Shininess, like `u_scene.specular`.
+vec3 blinn_phong(vec3 albedo, vec3 normal, vec3 light_dir, vec3 view_dir, vec2 specular) {
+    vec3 halfway = normalize(light_dir + view_dir);
+    float n_dot_l = max(dot(normal, light_dir), 0.0);
+    //  No highlights on the side facing away from the light
+    float highlight = n_dot_l > 0.0
+        ? specular.x * pow(max(dot(normal, halfway), 0.0), specular.y)
+        : 0.0;
+    return albedo * n_dot_l + highlight;
+}
+
+//  The ambient and directional light of `u_scene`, with the shadow.
+//  `light_position` is `world_position` in the shadow map's clip space.
+vec3 sun_lighting(vec3 albedo, vec3 normal, vec3 world_position, vec4 light_position, vec2 specular) {
+    vec3 light_dir = normalize(u_scene.light_dir.xyz);
+    vec3 view_dir = normalize(u_scene.camera_position.xyz - world_position);
+    float n_dot_l = max(dot(normal, light_dir), 0.0);
+    vec3 direct = blinn_phong(albedo, normal, light_dir, view_dir, specular)
+        * u_scene.light_color.rgb
+        * shadow(light_position, n_dot_l);
+    return albedo * u_scene.ambient_color.rgb + direct;
+}
@@ -0,0 +1,14 @@
+//  `UniformData` in uniform.rs, shared by vertex.vert and fragment.frag.
+layout(binding = 0) uniform UniformData {
+    vec4 color;
+    //  World space to the shadow map's clip space
+    mat4 light_view_proj;
+    //  Towards the light, in world space
+    vec4 light_dir;
+    vec4 light_color;
+    //  Light that comes from everywhere and is never in shadow
+    vec4 ambient_color;
+    vec4 camera_position;
+    //  x: Strength, y: Shininess
+    vec4 specular;
+} u_scene;
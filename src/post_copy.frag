@@ -0,0 +1,12 @@
+#version 450
+
+//  Used when every other effect is turned off.
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+void main() {
+
+    o_color = vec4(texture(u_input, i_uv).rgb, 1.0);
+
+}
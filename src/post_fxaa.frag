@@ -0,0 +1,61 @@
+#version 450
+
+//  The small FXAA from Timothy Lottes' original write-up.
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+layout(push_constant) uniform Params {
+    //  How much of the anti-aliased result to use
+    float strength;
+    //  How much local contrast, relative to the brightest pixel, makes an edge
+    float edge_threshold;
+};
+
+const float EDGE_THRESHOLD_MIN = 1.0 / 32.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+const float SPAN_MAX = 8.0;
+
+//  The input is linear, edges are found on something closer to perceived brightness.
+float luma(vec3 color) {
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 fetch(vec2 offset) {
+    return texture(u_input, i_uv + offset).rgb;
+}
+
+void main() {
+
+    vec2 texel = 1.0 / vec2(textureSize(u_input, 0));
+    vec3 rgb_m = fetch(vec2(0.0));
+    float luma_m = luma(rgb_m);
+    float luma_nw = luma(fetch(vec2(-1.0, -1.0) * texel));
+    float luma_ne = luma(fetch(vec2(1.0, -1.0) * texel));
+    float luma_sw = luma(fetch(vec2(-1.0, 1.0) * texel));
+    float luma_se = luma(fetch(vec2(1.0, 1.0) * texel));
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    if (luma_max - luma_min < max(EDGE_THRESHOLD_MIN, luma_max * edge_threshold)) {
+        o_color = vec4(rgb_m, 1.0);
+        return;
+    }
+
+    //  Blur along the edge
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, -SPAN_MAX, SPAN_MAX) * texel;
+
+    vec3 rgb_a = 0.5 * (fetch(dir * (1.0 / 3.0 - 0.5)) + fetch(dir * (2.0 / 3.0 - 0.5)));
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (fetch(dir * -0.5) + fetch(dir * 0.5));
+    float luma_b = luma(rgb_b);
+    vec3 result = (luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b;
+    o_color = vec4(mix(rgb_m, result, strength), 1.0);
+
+}
@@ -0,0 +1,17 @@
+#version 450
+
+//  Only needed when the swapchain isn't sRGB, otherwise the hardware encodes for us.
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+layout(push_constant) uniform Params {
+    float gamma;
+};
+
+void main() {
+
+    vec3 color = max(texture(u_input, i_uv).rgb, 0.0);
+    o_color = vec4(pow(color, vec3(1.0 / gamma)), 1.0);
+
+}
@@ -0,0 +1,49 @@
+#version 450
+
+//  Used instead of tonemap and gamma when the swapchain is HDR, so highlights keep their range.
+//  scRGB is linear Rec. 709 where 1.0 is 80 nits, HDR10 is Rec. 2020 through the PQ curve.
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+layout(push_constant) uniform Params {
+    float exposure;
+    //  How bright 1.0 in the scene is, in nits
+    float paper_white;
+    //  The brightest the display goes, highlights roll off towards it
+    float peak;
+    //  0 for scRGB, 1 for HDR10
+    float pq;
+};
+
+//  Columns, not rows
+const mat3 REC709_TO_REC2020 = mat3(
+    0.6274, 0.0691, 0.0164,
+    0.3293, 0.9195, 0.0880,
+    0.0433, 0.0114, 0.8956
+);
+
+//  Untouched up to half of `peak`, then eased into it
+vec3 rolloff(vec3 nits) {
+    float knee = 0.5 * peak;
+    vec3 over = max(nits - knee, 0.0);
+    return min(nits, knee) + (peak - knee) * (1.0 - exp(-over / (peak - knee)));
+}
+
+//  SMPTE ST 2084, from nits to [0, 1]
+vec3 pq_encode(vec3 nits) {
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(0.1593017578125));
+    return pow((0.8359375 + 18.8515625 * y) / (1.0 + 18.6875 * y), vec3(78.84375));
+}
+
+void main() {
+
+    vec3 hdr = max(texture(u_input, i_uv).rgb * exposure, 0.0);
+    vec3 nits = rolloff(hdr * paper_white);
+    if (pq > 0.5) {
+        o_color = vec4(pq_encode(REC709_TO_REC2020 * nits), 1.0);
+    } else {
+        o_color = vec4(nits / 80.0, 1.0);
+    }
+
+}
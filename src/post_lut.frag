@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+//  LUT_SIZE slices of LUT_SIZE x LUT_SIZE side by side, one per blue value.
+layout(binding = 1) uniform sampler2D u_lut;
+
+layout(push_constant) uniform Params {
+    float strength;
+};
+
+const float LUT_SIZE = 16.0;
+
+vec3 grade(vec3 color) {
+    color = clamp(color, 0.0, 1.0);
+    float blue = color.b * (LUT_SIZE - 1.0);
+    float slice_0 = floor(blue);
+    float slice_1 = min(slice_0 + 1.0, LUT_SIZE - 1.0);
+    vec2 uv = (color.rg * (LUT_SIZE - 1.0) + 0.5) / vec2(LUT_SIZE * LUT_SIZE, LUT_SIZE);
+    vec3 a = texture(u_lut, uv + vec2(slice_0 / LUT_SIZE, 0.0)).rgb;
+    vec3 b = texture(u_lut, uv + vec2(slice_1 / LUT_SIZE, 0.0)).rgb;
+    return mix(a, b, blue - slice_0);
+}
+
+void main() {
+
+    vec3 color = texture(u_input, i_uv).rgb;
+    o_color = vec4(mix(color, grade(color), strength), 1.0);
+
+}
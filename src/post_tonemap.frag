@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+layout(push_constant) uniform Params {
+    float exposure;
+};
+
+//  Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+
+    vec3 hdr = texture(u_input, i_uv).rgb;
+    o_color = vec4(aces(hdr * exposure), 1.0);
+
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+layout(binding = 0) uniform sampler2D u_input;
+
+layout(push_constant) uniform Params {
+    float strength;
+    //  Distance from the center where the darkening ends, and how far in it starts
+    float radius;
+    float softness;
+};
+
+void main() {
+
+    vec3 color = texture(u_input, i_uv).rgb;
+    float vignette = smoothstep(radius, radius - softness, length(i_uv - 0.5));
+    o_color = vec4(color * mix(1.0, vignette, strength), 1.0);
+
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+
+layout(push_constant) uniform Constants {
+    //  Straight into the light's clip space
+    mat4 mvp;
+};
+
+void main() {
+
+    gl_Position = mvp * vec4(a_position, 1.0);
+
+}
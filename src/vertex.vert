@@ -1,25 +1,40 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "scene.glsl"
 
 layout(location = 0) in vec3 a_position;
 layout(location = 1) in vec3 a_color;
 layout(location = 2) in vec2 a_tex_coord;
+layout(location = 3) in vec3 a_normal;
 
 layout(location = 0) out vec4 o_frag_color;
 layout(location = 1) out vec2 o_tex_coord;
+layout(location = 2) out vec3 o_world_position;
+layout(location = 3) out vec4 o_light_position;
+layout(location = 4) out vec3 o_normal;
 
 layout(push_constant) uniform Constants {
     mat4 mvp;
+    mat4 model;
 };
 
-layout(binding = 0) uniform UniformData {
-    vec4 color;
-} u_color;
+//  Set through `PipelineVariant::vert`.
+//  The ground keeps its own color instead of pulsing along with the cube.
+layout(constant_id = 0) const bool TINTED = true;
 
 void main() {
 
-    o_frag_color = vec4(a_color, 1.0) * u_color.color;
+    o_frag_color = vec4(a_color, 1.0);
+    if (TINTED) {
+        o_frag_color *= u_scene.color;
+    }
     o_tex_coord = a_tex_coord;
+    vec4 world_position = model * vec4(a_position, 1.0);
+    o_world_position = world_position.xyz;
+    o_light_position = u_scene.light_view_proj * world_position;
+    //  The ground is scaled unevenly, which would tilt its normal otherwise
+    o_normal = mat3(transpose(inverse(model))) * a_normal;
     gl_Position = mvp * vec4(a_position, 1.0);
 
 }
-
@@ -8,6 +8,7 @@ pub struct BabyVulkan {
     pub surface: vk::SurfaceKHR,
     pub queue_families: QueueFamilies,
     pub gpu: vk::PhysicalDevice,
+    pub gpu_properties: vk::PhysicalDeviceProperties,
     pub dev: Device,
     pub debug_ext: extensions::ext::DebugUtils,
     pub debug: vk::DebugUtilsMessengerEXT,
@@ -15,10 +16,18 @@ pub struct BabyVulkan {
     pub present_queue: vk::Queue,
     pub transfer_queue: vk::Queue,
     pub alloc: vk_mem::Allocator,
+    //  `None` if the device can't render without render passes, or we were asked not to.
+    pub dynamic_rendering: Option<DynamicRendering>,
+    //  `None` if submissions are waited on with fences, see `GpuWait`.
+    pub timeline: Option<Timeline>,
 }
 
 impl BabyVulkan {
-    pub fn create(window: &Window) -> Option<Self> {
+    pub fn create(
+        window: &Window,
+        want_dynamic_rendering: bool,
+        want_timeline: bool,
+    ) -> Option<Self> {
         let entry = Entry::linked();
         let layers_owned = [CString::new("VK_LAYER_KHRONOS_validation").ok()?];
         let layers: Vec<*const i8> = layers_owned.iter().map(|s| s.as_ptr()).collect();
@@ -26,13 +35,23 @@ impl BabyVulkan {
         //  Create Instance
         let app_info = vk::ApplicationInfo::builder()
             .application_name(CString::new("Hello World").ok()?.as_c_str())
-            .api_version(vk::API_VERSION_1_2)
+            .api_version(vk::API_VERSION_1_3)
             .build();
-        let extensions_owned = [
+        let mut extensions_owned = vec![
             extensions::ext::DebugUtils::name(),
             extensions::khr::Surface::name(),
             extensions::khr::XlibSurface::name(),
         ];
+        //  Without it, surfaces only ever offer sRGB color spaces
+        let instance_extensions = entry
+            .enumerate_instance_extension_properties(None)
+            .unwrap_or_default();
+        if instance_extensions.iter().any(|ext| {
+            let name = unsafe { std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) };
+            name == vk::ExtSwapchainColorspaceFn::name()
+        }) {
+            extensions_owned.push(vk::ExtSwapchainColorspaceFn::name());
+        }
         let extensions: Vec<*const i8> = extensions_owned.iter().map(|s| s.as_ptr()).collect();
         let inst_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
@@ -64,12 +83,13 @@ impl BabyVulkan {
             .into_iter()
             .filter_map(|gpu| {
                 //  A suitable gpu must
-                //      1: Support graphics + present + tranfer
+                //      1: Support graphics + present + tranfer + compute
                 QueueFamilies::create(&instance, gpu, surface, &surface_ext)
                     .map(|queue_families| (gpu, queue_families))
             })
             .collect();
         let (gpu, queue_families) = suitable_gpus.into_iter().next()?;
+        let gpu_properties = unsafe { instance.get_physical_device_properties(gpu) };
 
         //  Create Device
         let queue_infos = [
@@ -89,17 +109,59 @@ impl BabyVulkan {
             //      .build(),
         ];
         let features = vk::PhysicalDeviceFeatures::builder().build();
-        let extensions: Vec<*const i8> = [extensions::khr::Swapchain::name()]
-            .into_iter()
-            .map(|s| s.as_ptr())
-            .collect();
-        let dev_info = vk::DeviceCreateInfo::builder()
+        let dynamic_rendering_support = if want_dynamic_rendering {
+            DynamicRendering::support(&instance, gpu, gpu_properties.api_version)
+        } else {
+            None
+        };
+        let mut extensions_owned = vec![extensions::khr::Swapchain::name()];
+        if dynamic_rendering_support == Some(DynamicRenderingSupport::Khr) {
+            extensions_owned.push(extensions::khr::DynamicRendering::name());
+        }
+        let extensions: Vec<*const i8> = extensions_owned.iter().map(|s| s.as_ptr()).collect();
+        let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::builder()
+            .dynamic_rendering(true)
+            .build();
+        let timeline_support = want_timeline && timeline_support(&instance, gpu, &gpu_properties);
+        let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::builder()
+            .timeline_semaphore(true)
+            .build();
+        let mut dev_info = vk::DeviceCreateInfo::builder()
             .enabled_extension_names(&extensions)
             // .enabled_layer_names(&layers)
             .enabled_features(&features)
-            .queue_create_infos(&queue_infos)
-            .build();
+            .queue_create_infos(&queue_infos);
+        if dynamic_rendering_support.is_some() {
+            dev_info = dev_info.push_next(&mut dynamic_rendering_features);
+        }
+        if timeline_support {
+            dev_info = dev_info.push_next(&mut timeline_features);
+        }
+        let dev_info = dev_info.build();
         let dev = unsafe { instance.create_device(gpu, &dev_info, None) }.ok()?;
+        let dynamic_rendering = dynamic_rendering_support.map(|support| match support {
+            DynamicRenderingSupport::Core => DynamicRendering::Core,
+            DynamicRenderingSupport::Khr => {
+                DynamicRendering::Khr(extensions::khr::DynamicRendering::new(&instance, &dev))
+            }
+        });
+        println!(
+            "[BabyVulkan] Rendering with {}",
+            match dynamic_rendering {
+                Some(DynamicRendering::Core) => "dynamic rendering",
+                Some(DynamicRendering::Khr(_)) => "VK_KHR_dynamic_rendering",
+                None => "render passes",
+            }
+        );
+
+        let timeline = if timeline_support {
+            Some(Timeline::create(&dev)?)
+        } else {
+            if want_timeline {
+                println!("[BabyVulkan] No timeline semaphores, using fences");
+            }
+            None
+        };
 
         //  Get the queues
         let present_queue = unsafe { dev.get_device_queue(queue_families.present, 0) };
@@ -116,6 +178,7 @@ impl BabyVulkan {
             surface,
             surface_ext,
             gpu,
+            gpu_properties,
             queue_families,
             dev,
             debug_ext,
@@ -124,10 +187,15 @@ impl BabyVulkan {
             present_queue,
             transfer_queue,
             alloc,
+            dynamic_rendering,
+            timeline,
         })
     }
 
     pub fn destroy(&mut self) {
+        if let Some(timeline) = self.timeline.take() {
+            timeline.destroy(&self.dev);
+        }
         unsafe {
             self.surface_ext.destroy_surface(self.surface, None);
             self.dev.destroy_device(None);
@@ -159,6 +227,40 @@ impl BabyVulkan {
         })
     }
 
+    //  The first of `candidates` that can be used with optimal tiling for everything in `features`.
+    pub fn find_format(
+        &self,
+        candidates: &[vk::Format],
+        features: vk::FormatFeatureFlags,
+    ) -> Option<vk::Format> {
+        candidates.iter().copied().find(|&format| {
+            let props = unsafe {
+                self.instance
+                    .get_physical_device_format_properties(self.gpu, format)
+            };
+            props.optimal_tiling_features.contains(features)
+        })
+    }
+
+    //  The most samples up to `requested` that color, depth and stencil attachments can all have.
+    pub fn get_sample_count(&self, requested: u32) -> vk::SampleCountFlags {
+        let limits = &self.gpu_properties.limits;
+        let supported = limits.framebuffer_color_sample_counts
+            & limits.framebuffer_depth_sample_counts
+            & limits.framebuffer_stencil_sample_counts;
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&samples| samples.as_raw() <= requested && supported.contains(samples))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+
     pub fn create_image_view(
         &self,
         image: vk::Image,
@@ -228,6 +330,141 @@ impl BabyVulkan {
             .build();
         unsafe { self.dev.create_fence(&fence_info, None) }.ok()
     }
+
+    //  Submit `cmd_bufs` so that they signal `done`, on top of the binary `signal` semaphores.
+    pub fn submit(
+        &self,
+        queue: vk::Queue,
+        cmd_bufs: &[vk::CommandBuffer],
+        waits: &[(vk::Semaphore, vk::PipelineStageFlags)],
+        signal: &[vk::Semaphore],
+        done: GpuWait,
+    ) -> Option<()> {
+        let wait_semaphores: Vec<vk::Semaphore> = waits.iter().map(|&(s, _)| s).collect();
+        let wait_stages: Vec<vk::PipelineStageFlags> = waits.iter().map(|&(_, s)| s).collect();
+        let mut signal_semaphores = signal.to_vec();
+        //  Binary semaphores ignore their value, but there has to be one for each
+        let mut signal_values = vec![0; signal.len()];
+        let fence = match (done, &self.timeline) {
+            (GpuWait::Fence(fence), _) => fence,
+            (GpuWait::Timeline(value), Some(timeline)) => {
+                signal_semaphores.push(timeline.semaphore);
+                signal_values.push(value);
+                vk::Fence::null()
+            }
+            (GpuWait::Timeline(_), None) => None?,
+        };
+        let wait_values = vec![0; waits.len()];
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values)
+            .build();
+        let mut submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .signal_semaphores(&signal_semaphores)
+            .command_buffers(cmd_bufs);
+        if self.timeline.is_some() {
+            submit_info = submit_info.push_next(&mut timeline_info);
+        }
+        unsafe { self.dev.queue_submit(queue, &[submit_info.build()], fence) }.ok()
+    }
+
+    //  For uploads and the like, which are done before going on.
+    //  `fence` is only used without a timeline.
+    pub fn submit_and_wait(
+        &self,
+        queue: vk::Queue,
+        cmd_buf: vk::CommandBuffer,
+        fence: vk::Fence,
+    ) -> Option<()> {
+        let done = GpuWait::next(self, fence)?;
+        self.submit(queue, &[cmd_buf], &[], &[], done)?;
+        done.wait(self)
+    }
+}
+
+//  Where `vkCmdBeginRendering` comes from.
+pub enum DynamicRendering {
+    //  Vulkan 1.3
+    Core,
+    Khr(extensions::khr::DynamicRendering),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DynamicRenderingSupport {
+    Core,
+    Khr,
+}
+
+impl DynamicRendering {
+    //  Whether `gpu` can do it, and how. Has to be known before the device is created.
+    fn support(
+        instance: &Instance,
+        gpu: vk::PhysicalDevice,
+        api_version: u32,
+    ) -> Option<DynamicRenderingSupport> {
+        let support = if vk::api_version_minor(api_version) >= 3 {
+            DynamicRenderingSupport::Core
+        } else {
+            let extensions = unsafe { instance.enumerate_device_extension_properties(gpu) }.ok()?;
+            extensions
+                .iter()
+                .find(|ext| {
+                    let name = unsafe { std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) };
+                    name == extensions::khr::DynamicRendering::name()
+                })
+                .map(|_| DynamicRenderingSupport::Khr)?
+        };
+
+        //  The extension being there doesn't mean the feature is
+        let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+        let mut features = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut dynamic_rendering_features)
+            .build();
+        unsafe { instance.get_physical_device_features2(gpu, &mut features) };
+        (dynamic_rendering_features.dynamic_rendering == vk::TRUE).then_some(support)
+    }
+
+    pub fn cmd_begin_rendering(
+        &self,
+        bvk: &BabyVulkan,
+        cmd_buf: vk::CommandBuffer,
+        rendering_info: &vk::RenderingInfo,
+    ) {
+        unsafe {
+            match self {
+                DynamicRendering::Core => bvk.dev.cmd_begin_rendering(cmd_buf, rendering_info),
+                DynamicRendering::Khr(ext) => ext.cmd_begin_rendering(cmd_buf, rendering_info),
+            }
+        }
+    }
+
+    pub fn cmd_end_rendering(&self, bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer) {
+        unsafe {
+            match self {
+                DynamicRendering::Core => bvk.dev.cmd_end_rendering(cmd_buf),
+                DynamicRendering::Khr(ext) => ext.cmd_end_rendering(cmd_buf),
+            }
+        }
+    }
+}
+
+//  Core in Vulkan 1.2, but still a feature that has to be turned on.
+fn timeline_support(
+    instance: &Instance,
+    gpu: vk::PhysicalDevice,
+    gpu_properties: &vk::PhysicalDeviceProperties,
+) -> bool {
+    if vk::api_version_minor(gpu_properties.api_version) < 2 {
+        return false;
+    }
+    let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut timeline_features)
+        .build();
+    unsafe { instance.get_physical_device_features2(gpu, &mut features) };
+    timeline_features.timeline_semaphore == vk::TRUE
 }
 
 pub struct QueueFamilies {
@@ -249,7 +486,12 @@ impl QueueFamilies {
         let queue_family_props = unsafe { inst.get_physical_device_queue_family_properties(gpu) };
         for (idx, prop) in queue_family_props.iter().enumerate() {
             let idx = idx as u32;
-            if prop.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
+            //  Compute dispatches are recorded right next to the draws that use them.
+            //  Vulkan promises at least one family that can do both.
+            if prop
+                .queue_flags
+                .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            {
                 graphics.get_or_insert(idx);
             }
             if prop.queue_flags.contains(vk::QueueFlags::TRANSFER) {
@@ -1,10 +1,15 @@
 use super::*;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub color: [f32; 3],
     pub uv: [f32; 2],
+    pub normal: [f32; 3],
+    //  Points along increasing u. w is 1 or -1, the bitangent (increasing v) is
+    //  `cross(normal, tangent) * w`.
+    pub tangent: [f32; 4],
 }
 
 impl Vertex {
@@ -16,26 +21,41 @@ impl Vertex {
             .build()
     }
 
-    pub fn attributes() -> [vk::VertexInputAttributeDescription; 3] {
-        [
-            vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .location(0)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .offset(std::mem::size_of::<[f32; 3]>() as u32 * 0)
-                .build(),
+    pub fn attributes() -> [vk::VertexInputAttributeDescription; 5] {
+        let attribute = |location, format, offset: usize| {
             vk::VertexInputAttributeDescription::builder()
                 .binding(0)
-                .location(1)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .offset(std::mem::size_of::<[f32; 3]>() as u32 * 1)
-                .build(),
-            vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .location(2)
-                .format(vk::Format::R32G32_SFLOAT)
-                .offset(std::mem::size_of::<[f32; 3]>() as u32 * 2)
-                .build(),
+                .location(location)
+                .format(format)
+                .offset(offset as u32)
+                .build()
+        };
+        [
+            attribute(
+                0,
+                vk::Format::R32G32B32_SFLOAT,
+                std::mem::offset_of!(Vertex, position),
+            ),
+            attribute(
+                1,
+                vk::Format::R32G32B32_SFLOAT,
+                std::mem::offset_of!(Vertex, color),
+            ),
+            attribute(
+                2,
+                vk::Format::R32G32_SFLOAT,
+                std::mem::offset_of!(Vertex, uv),
+            ),
+            attribute(
+                3,
+                vk::Format::R32G32B32_SFLOAT,
+                std::mem::offset_of!(Vertex, normal),
+            ),
+            attribute(
+                4,
+                vk::Format::R32G32B32A32_SFLOAT,
+                std::mem::offset_of!(Vertex, tangent),
+            ),
         ]
     }
 }
@@ -115,16 +135,9 @@ impl Buffer {
 
             bvk.dev.end_command_buffer(cmd_buf).ok()?;
 
-            let submit_info = vk::SubmitInfo::builder()
-                .command_buffers(&[cmd_buf])
-                .build();
-            bvk.dev
-                .queue_submit(bvk.transfer_queue, &[submit_info], fence)
-                .ok()?;
-            bvk.dev.wait_for_fences(&[fence], true, u64::MAX).ok()?;
+            bvk.submit_and_wait(bvk.transfer_queue, cmd_buf, fence)?;
 
             //  Cleanup
-            bvk.dev.reset_fences(&[fence]).ok()?;
             bvk.dev
                 .reset_command_buffer(cmd_buf, vk::CommandBufferResetFlags::empty())
                 .ok()?;
@@ -142,14 +155,6 @@ impl Buffer {
 #[repr(C)]
 pub struct PushConstantData {
     pub mvp: glm::Mat4,
-}
-
-impl PushConstantData {
-    pub fn push_constants() -> vk::PushConstantRange {
-        vk::PushConstantRange::builder()
-            .offset(0)
-            .size(std::mem::size_of::<Self>() as u32)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .build()
-    }
+    //  For lighting, which happens in world space
+    pub model: glm::Mat4,
 }
@@ -0,0 +1,255 @@
+use super::*;
+
+#[repr(C)]
+pub struct AnimatePushConstantData {
+    pub time: f32,
+    pub vertex_count: u32,
+}
+
+#[repr(C)]
+pub struct PatternPushConstantData {
+    pub time: f32,
+}
+
+pub struct ComputePipeline {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    pub local_size: [u32; 3],
+    pub shader: vk::ShaderModule,
+}
+
+impl ComputePipeline {
+    pub fn create(
+        bvk: &BabyVulkan,
+        code: &[u32],
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    ) -> Option<Self> {
+        let mut interface = ShaderInterface::reflect(&[code])
+            .map_err(|e| println!("[Compute] {}", e))
+            .ok()?;
+
+        //  Create the Shader
+        let shader_info = vk::ShaderModuleCreateInfo::builder().code(code).build();
+        let shader = unsafe { bvk.dev.create_shader_module(&shader_info, None) }.ok()?;
+        let entry_point = CString::new("main").ok()?;
+        let stage_info = vk::PipelineShaderStageCreateInfo::builder()
+            .name(&entry_point)
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader)
+            .build();
+
+        //  Create Pipeline Layout
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&interface.push_constants)
+            .set_layouts(descriptor_set_layouts)
+            .build();
+        let pipeline_layout =
+            unsafe { bvk.dev.create_pipeline_layout(&pipeline_layout_info, None) }.ok()?;
+
+        //  Create the Compute Pipeline
+        //  So much simpler than the graphics one!
+        let compute_pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage_info)
+            .layout(pipeline_layout)
+            .build();
+        let pipeline = unsafe {
+            bvk.dev.create_compute_pipelines(
+                vk::PipelineCache::null(),
+                &[compute_pipeline_info],
+                None,
+            )
+        }
+        .ok()?
+        .into_iter()
+        .next()?;
+
+        Some(ComputePipeline {
+            pipeline,
+            pipeline_layout,
+            push_constant_ranges: std::mem::take(&mut interface.push_constants),
+            local_size: interface.local_size,
+            shader,
+        })
+    }
+
+    //  Runs at least `invocations` threads, rounded up to whole workgroups of `local_size`.
+    //  Note that this does not insert any barriers, the render graph takes care of those.
+    pub fn dispatch(
+        &self,
+        bvk: &BabyVulkan,
+        cmd_buf: vk::CommandBuffer,
+        descriptor_sets: &[vk::DescriptorSet],
+        push_constants: &[u8],
+        invocations: [u32; 3],
+    ) {
+        let group_counts: [u32; 3] =
+            std::array::from_fn(|i| invocations[i].div_ceil(self.local_size[i]));
+        unsafe {
+            bvk.dev
+                .cmd_bind_pipeline(cmd_buf, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            bvk.dev.cmd_bind_descriptor_sets(
+                cmd_buf,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                descriptor_sets,
+                &[],
+            );
+            cmd_push_constants(
+                bvk,
+                cmd_buf,
+                self.pipeline_layout,
+                &self.push_constant_ranges,
+                push_constants,
+            );
+            bvk.dev
+                .cmd_dispatch(cmd_buf, group_counts[0], group_counts[1], group_counts[2]);
+        }
+    }
+
+    pub fn destroy(&self, bvk: &BabyVulkan) {
+        unsafe {
+            bvk.dev.destroy_shader_module(self.shader, None);
+            bvk.dev.destroy_pipeline(self.pipeline, None);
+            bvk.dev.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+//  What goes into each binding of a `ComputeDescriptors`.
+pub enum ComputeResource<'a> {
+    StorageBuffer(&'a Buffer),
+    //  Storage images must be in `GENERAL` when dispatching, see `PassBuilder::write_storage_image`.
+    StorageImage(vk::ImageView),
+}
+
+//  A single descriptor set for a compute shader.
+//  Unlike `Uniform`, there's only one since nothing here is written by the CPU each frame.
+pub struct ComputeDescriptors {
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl ComputeDescriptors {
+    //  `bindings` should come from `ShaderInterface::reflect`, and `resources[i]` goes into
+    //  `bindings[i]`.
+    pub fn create(
+        bvk: &BabyVulkan,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+        resources: &[ComputeResource],
+    ) -> Option<Self> {
+        if bindings.len() != resources.len() {
+            println!(
+                "[Compute] Shader has {} bindings, but we have {} resources",
+                bindings.len(),
+                resources.len()
+            );
+            None?;
+        }
+
+        //  Create Descriptor Set Layout
+        let descriptor_set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+            .build();
+        let descriptor_set_layout = unsafe {
+            bvk.dev
+                .create_descriptor_set_layout(&descriptor_set_layout_info, None)
+        }
+        .ok()?;
+
+        //  Create Descriptor Pool
+        let descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorPoolSize::builder()
+                    .descriptor_count(binding.descriptor_count)
+                    .ty(binding.descriptor_type)
+                    .build()
+            })
+            .collect();
+        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&descriptor_pool_sizes)
+            .max_sets(1)
+            .build();
+        let descriptor_pool =
+            unsafe { bvk.dev.create_descriptor_pool(&descriptor_pool_info, None) }.ok()?;
+
+        //  Create Descriptor Set
+        let descriptor_set_info = vk::DescriptorSetAllocateInfo::builder()
+            .set_layouts(&[descriptor_set_layout])
+            .descriptor_pool(descriptor_pool)
+            .build();
+        let descriptor_set = unsafe { bvk.dev.allocate_descriptor_sets(&descriptor_set_info) }
+            .ok()?
+            .into_iter()
+            .next()?;
+
+        //  Configure Descriptor Set
+        //  The infos have to outlive the writes, so collect them first.
+        let buffer_infos: Vec<vk::DescriptorBufferInfo> = resources
+            .iter()
+            .map(|resource| match resource {
+                ComputeResource::StorageBuffer(buf) => vk::DescriptorBufferInfo::builder()
+                    .buffer(buf.buf)
+                    .offset(0)
+                    .range(vk::WHOLE_SIZE)
+                    .build(),
+                _ => vk::DescriptorBufferInfo::default(),
+            })
+            .collect();
+        let image_infos: Vec<vk::DescriptorImageInfo> = resources
+            .iter()
+            .map(|resource| match resource {
+                ComputeResource::StorageImage(view) => vk::DescriptorImageInfo::builder()
+                    .image_view(*view)
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .build(),
+                _ => vk::DescriptorImageInfo::default(),
+            })
+            .collect();
+        let descriptor_writes = bindings
+            .iter()
+            .zip(resources.iter())
+            .enumerate()
+            .map(|(idx, (binding, resource))| {
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(binding.binding)
+                    .dst_array_element(0)
+                    .descriptor_type(binding.descriptor_type);
+                match (binding.descriptor_type, resource) {
+                    (vk::DescriptorType::STORAGE_BUFFER, ComputeResource::StorageBuffer(_)) => {
+                        Some(write.buffer_info(&buffer_infos[idx..idx + 1]).build())
+                    }
+                    (vk::DescriptorType::STORAGE_IMAGE, ComputeResource::StorageImage(_)) => {
+                        Some(write.image_info(&image_infos[idx..idx + 1]).build())
+                    }
+                    (ty, _) => {
+                        println!(
+                            "[Compute] Binding {} is {:?}, which doesn't match its resource",
+                            binding.binding, ty
+                        );
+                        None
+                    }
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        unsafe { bvk.dev.update_descriptor_sets(&descriptor_writes, &[]) };
+
+        Some(ComputeDescriptors {
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+        })
+    }
+
+    pub fn destroy(&self, bvk: &BabyVulkan) {
+        unsafe {
+            bvk.dev
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            bvk.dev.destroy_descriptor_pool(self.descriptor_pool, None);
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use super::*;
+
+//  Same as in deferred_lighting.frag
+pub const MAX_POINT_LIGHTS: usize = 64;
+
+//  `layout(binding = N)` in deferred_lighting.frag
+const SCENE_BINDING: u32 = 0;
+const SHADOW_MAP_BINDING: u32 = 4;
+const LIGHTS_BINDING: u32 = 5;
+//  Albedo, Normal, Material, Depth
+const GBUFFER_BINDINGS: [u32; 4] = [6, 7, 8, 9];
+
+//  What gbuffer.frag writes, as (Name, Format).
+//  Albedo comes from sRGB textures so it stays sRGB, normals need the range and the precision.
+const GBUFFER: [(&str, vk::Format); 3] = [
+    ("gbuffer_albedo", vk::Format::R8G8B8A8_SRGB),
+    ("gbuffer_normal", vk::Format::R16G16B16A16_SFLOAT),
+    ("gbuffer_material", vk::Format::R8G8B8A8_UNORM),
+];
+
+//  Best first. The depth is read back as an input attachment, and views of those can only have one
+//  aspect, so none of them have a stencil.
+const GBUFFER_DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::X8_D24_UNORM_PACK32,
+    vk::Format::D16_UNORM,
+];
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PointLight {
+    //  w: How far the light reaches
+    pub position: glm::Vec4,
+    pub color: glm::Vec4,
+}
+
+//  `LightData` in deferred_lighting.frag
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LightData {
+    pub inverse_view_proj: glm::Mat4,
+    //  x: How many of `point_lights` are used
+    pub count: [u32; 4],
+    pub point_lights: [PointLight; MAX_POINT_LIGHTS],
+}
+
+impl LightData {
+    //  Only the first `MAX_POINT_LIGHTS` of `point_lights` fit
+    pub fn new(inverse_view_proj: glm::Mat4, point_lights: &[PointLight]) -> Self {
+        let count = point_lights.len().min(MAX_POINT_LIGHTS);
+        let mut data = LightData {
+            inverse_view_proj,
+            count: [count as u32, 0, 0, 0],
+            point_lights: [PointLight {
+                position: glm::Vec4::zeros(),
+                color: glm::Vec4::zeros(),
+            }; MAX_POINT_LIGHTS],
+        };
+        data.point_lights[..count].copy_from_slice(&point_lights[..count]);
+        data
+    }
+}
+
+//  Draws the scene in one pass with two subpasses, instead of lighting every object as it's drawn.
+//  The first subpass fills a G-buffer with what every pixel looks like, the second one reads it
+//  back as input attachments and adds up the lights once per pixel. That way, the cost of the
+//  lights doesn't grow with the number of objects.
+//  There is no MSAA, input attachments would have to be read one sample at a time.
+pub struct Deferred {
+    pub depth_format: vk::Format,
+    //  For the first subpass: the scene's uniforms and the texture
+    pub uniform: Uniform,
+    //  `TransientBuffer::buffer`, the lighting reads `UniformData` and `LightData` from it
+    uniform_buf: vk::Buffer,
+    lighting_set_layout: vk::DescriptorSetLayout,
+    //  With dynamic uniform buffers
+    lighting_bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    shadow_map_info: vk::DescriptorImageInfo,
+    descriptor_pool: vk::DescriptorPool,
+    //  Per frame in flight, since each one points at its own G-buffer
+    lighting_sets: Vec<vk::DescriptorSet>,
+    vert_code: Vec<u32>,
+    gbuffer_code: Vec<u32>,
+    fullscreen_code: Vec<u32>,
+    lighting_code: Vec<u32>,
+    //  Only known once the graph made the render pass, see `prepare`.
+    //  (Geometry, Lighting)
+    pass_infos: Option<(PassInfo, PassInfo)>,
+    pipelines: PipelineVariants,
+    lighting_pipeline: Option<VulkanPipeline>,
+    //  This frame's pass, and what the lighting reads in which layout, see `add_pass`
+    pass: Option<PassHandle>,
+    inputs: Vec<(ImageHandle, vk::ImageLayout)>,
+}
+
+impl Deferred {
+    //  `uniform_buf` is `TransientBuffer::buffer`.
+    pub fn create(
+        bvk: &BabyVulkan,
+        texture: &Texture,
+        shadow_map: &Texture,
+        uniform_buf: vk::Buffer,
+        frame_count: usize,
+    ) -> Option<Self> {
+        let depth_format = bvk.find_format(
+            &GBUFFER_DEPTH_FORMATS,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )?;
+        let vert_code = VulkanPipeline::read_shader_code(shaders::VERTEX)?;
+        let gbuffer_code = VulkanPipeline::read_shader_code(shaders::GBUFFER)?;
+        let fullscreen_code = VulkanPipeline::read_shader_code(shaders::FULLSCREEN)?;
+        let lighting_code = VulkanPipeline::read_shader_code(shaders::DEFERRED_LIGHTING)?;
+
+        //  Derive the Descriptor Set Layouts from the Shaders
+        let reflect = |stages: &[&[u32]]| {
+            ShaderInterface::reflect(stages)
+                .and_then(|interface| Ok(interface.descriptor_set()?.to_vec()))
+                .map_err(|e| println!("[Deferred] {}", e))
+                .ok()
+        };
+        let gbuffer_bindings = reflect(&[&vert_code, &gbuffer_code])?;
+        let lighting_bindings = dynamic_bindings(&reflect(&[&fullscreen_code, &lighting_code])?);
+        let uniform = Uniform::create(bvk, &gbuffer_bindings, &[(1, texture)], uniform_buf)?;
+
+        //  Create Descriptor Set Layout
+        let lighting_set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&lighting_bindings)
+            .build();
+        let lighting_set_layout = unsafe {
+            bvk.dev
+                .create_descriptor_set_layout(&lighting_set_layout_info, None)
+        }
+        .ok()?;
+
+        let mut deferred = Deferred {
+            depth_format,
+            uniform,
+            uniform_buf,
+            lighting_set_layout,
+            lighting_bindings,
+            shadow_map_info: vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(shadow_map.image_view)
+                .sampler(shadow_map.sampler)
+                .build(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            lighting_sets: vec![],
+            vert_code,
+            gbuffer_code,
+            fullscreen_code,
+            lighting_code,
+            pass_infos: None,
+            pipelines: PipelineVariants::default(),
+            lighting_pipeline: None,
+            pass: None,
+            inputs: vec![],
+        };
+        deferred.create_frames(bvk, frame_count)?;
+        Some(deferred)
+    }
+
+    //  Nothing may be in flight.
+    pub fn set_frame_count(&mut self, bvk: &BabyVulkan, frame_count: usize) -> Option<()> {
+        self.destroy_frames(bvk);
+        self.create_frames(bvk, frame_count)
+    }
+
+    //  The lighting's descriptor sets, with everything but the G-buffer.
+    fn create_frames(&mut self, bvk: &BabyVulkan, frame_count: usize) -> Option<()> {
+        //  Create Descriptor Pool
+        let descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = self
+            .lighting_bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorPoolSize::builder()
+                    .descriptor_count(binding.descriptor_count * frame_count as u32)
+                    .ty(binding.descriptor_type)
+                    .build()
+            })
+            .collect();
+        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&descriptor_pool_sizes)
+            .max_sets(frame_count as u32)
+            .build();
+        self.descriptor_pool =
+            unsafe { bvk.dev.create_descriptor_pool(&descriptor_pool_info, None) }.ok()?;
+
+        //  Create Descriptor Sets
+        //  The G-buffer is filled in by `prepare`, once the graph found images for it.
+        let set_layouts = vec![self.lighting_set_layout; frame_count];
+        let descriptor_sets_info = vk::DescriptorSetAllocateInfo::builder()
+            .set_layouts(&set_layouts)
+            .descriptor_pool(self.descriptor_pool)
+            .build();
+        self.lighting_sets =
+            unsafe { bvk.dev.allocate_descriptor_sets(&descriptor_sets_info) }.ok()?;
+        for &set in &self.lighting_sets {
+            let buffer_info = |size: usize| {
+                vk::DescriptorBufferInfo::builder()
+                    .buffer(self.uniform_buf)
+                    .offset(0)
+                    .range(size as u64)
+                    .build()
+            };
+            let scene_info = buffer_info(std::mem::size_of::<UniformData>());
+            let lights_info = buffer_info(std::mem::size_of::<LightData>());
+            let write = |binding, ty| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(ty)
+            };
+            let writes = [
+                write(SCENE_BINDING, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                    .buffer_info(std::slice::from_ref(&scene_info))
+                    .build(),
+                write(LIGHTS_BINDING, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                    .buffer_info(std::slice::from_ref(&lights_info))
+                    .build(),
+                write(
+                    SHADOW_MAP_BINDING,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                )
+                .image_info(std::slice::from_ref(&self.shadow_map_info))
+                .build(),
+            ];
+            unsafe { bvk.dev.update_descriptor_sets(&writes, &[]) }
+        }
+        Some(())
+    }
+
+    fn destroy_frames(&mut self, bvk: &BabyVulkan) {
+        self.lighting_sets.clear();
+        unsafe {
+            bvk.dev.destroy_descriptor_pool(self.descriptor_pool, None);
+        }
+        self.descriptor_pool = vk::DescriptorPool::null();
+    }
+
+    //  Adds the pass, its G-buffer and the scene it lights to `graph`.
+    //  Returns the pass, so that whatever the scene reads can be added to it, and the scene itself.
+    pub fn add_pass<'a>(
+        &mut self,
+        graph: &'a mut RenderGraph,
+        extent: vk::Extent2D,
+        clear_color: [f32; 4],
+    ) -> (PassBuilder<'a>, ImageHandle) {
+        let gbuffer = GBUFFER.map(|(name, format)| {
+            graph.create_image(name, ImageDesc::subpass_input(format, extent))
+        });
+        let depth = graph.create_image(
+            "gbuffer_depth",
+            ImageDesc::subpass_input(self.depth_format, extent),
+        );
+        let scene = graph.create_image(
+            "scene",
+            ImageDesc::color(HDR_FORMAT, extent, vk::SampleCountFlags::TYPE_1),
+        );
+
+        let mut pass = graph.add_pass("deferred", PassKind::Graphics);
+        //  Create the G-buffer
+        let gbuffer =
+            gbuffer.map(|image| pass.color(image, LoadOp::Clear(vk::ClearValue::default())));
+        let depth = pass.depth(depth, LoadOp::Clear(DEPTH_CLEAR_VALUE));
+
+        //  Light it
+        pass.next_subpass();
+        for image in gbuffer.into_iter().chain([depth]) {
+            pass.input(image);
+        }
+        self.inputs = gbuffer
+            .map(|image| (image, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL))
+            .into_iter()
+            .chain([(depth, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)])
+            .collect();
+        let scene = pass.color(
+            scene,
+            LoadOp::Clear(vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear_color,
+                },
+            }),
+        );
+        self.pass = Some(pass.handle());
+        (pass, scene)
+    }
+
+    //  Points this frame's lighting at the G-buffer from `compiled`, and rebuilds the pipelines
+    //  if the pass changed underneath them.
+    //  `frame` has to be done on the GPU, its descriptor set is written to.
+    pub fn prepare(
+        &mut self,
+        bvk: &BabyVulkan,
+        compiled: &CompiledGraph,
+        frame: usize,
+    ) -> Option<()> {
+        let pass = match self.pass {
+            Some(pass) if compiled.runs(pass) => pass,
+            _ => return Some(()),
+        };
+        let pass_infos = (compiled.pass_info(pass, 0)?, compiled.pass_info(pass, 1)?);
+        let stale = match &self.pass_infos {
+            Some((geometry, _)) => {
+                geometry.render_pass != pass_infos.0.render_pass
+                    || geometry.extent != pass_infos.0.extent
+            }
+            None => true,
+        };
+        if stale {
+            let lighting_pipeline = self.create_lighting_pipeline(bvk, &pass_infos.1)?;
+            self.destroy_pipelines(bvk);
+            self.lighting_pipeline = Some(lighting_pipeline);
+            self.pass_infos = Some(pass_infos);
+        }
+
+        //  Update Descriptor Sets
+        let image_infos = self
+            .inputs
+            .iter()
+            .map(|&(input, layout)| {
+                Some(
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(layout)
+                        .image_view(compiled.view(input)?)
+                        .build(),
+                )
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let writes: Vec<vk::WriteDescriptorSet> = GBUFFER_BINDINGS
+            .iter()
+            .zip(&image_infos)
+            .map(|(&binding, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(self.lighting_sets[frame])
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+                    .image_info(std::slice::from_ref(image_info))
+                    .build()
+            })
+            .collect();
+        unsafe { bvk.dev.update_descriptor_sets(&writes, &[]) }
+        Some(())
+    }
+
+    //  Grab (or build) the pipeline that draws `variant` into the G-buffer.
+    //  `prepare` has to have been called first.
+    pub fn pipeline(
+        &mut self,
+        bvk: &BabyVulkan,
+        variant: &PipelineVariant,
+    ) -> Option<&VulkanPipeline> {
+        let (geometry, _) = self.pass_infos.as_ref()?;
+        self.pipelines.get_or_create(variant, || {
+            Self::create_geometry_pipeline(
+                bvk,
+                geometry,
+                &self.vert_code,
+                &self.gbuffer_code,
+                variant,
+                self.uniform.descriptor_set_layout,
+            )
+        })
+    }
+
+    //  Record the second subpass, after the geometry was drawn into the first one.
+    //  `scene` and `lights` are the dynamic offsets of this frame's `UniformData` and `LightData`.
+    pub fn record_lighting(
+        &self,
+        bvk: &BabyVulkan,
+        cmd_buf: vk::CommandBuffer,
+        frame: usize,
+        scene: u32,
+        lights: u32,
+    ) {
+        cmd_next_subpass(bvk, cmd_buf);
+        let pipeline = match &self.lighting_pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        unsafe {
+            bvk.dev
+                .cmd_bind_pipeline(cmd_buf, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline);
+            bvk.dev.cmd_bind_descriptor_sets(
+                cmd_buf,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[self.lighting_sets[frame]],
+                //  In binding order
+                &[scene, lights],
+            );
+            bvk.dev.cmd_draw(cmd_buf, 3, 1, 0, 0);
+        }
+    }
+
+    //  Swap in a new version of `shader`, if it is one of ours.
+    //  If the pipelines can't be built, the old ones are kept.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload(&mut self, bvk: &BabyVulkan, shader: &str, code: Vec<u32>) -> Option<()> {
+        let old_code = std::mem::replace(self.code_mut(shader)?, code);
+        //  Nothing to check against until the pass ran once, `prepare` builds them then.
+        let (geometry, lighting) = match self.pass_infos.clone() {
+            Some(pass_infos) => pass_infos,
+            None => return Some(()),
+        };
+        let geometry_pipeline = Self::create_geometry_pipeline(
+            bvk,
+            &geometry,
+            &self.vert_code,
+            &self.gbuffer_code,
+            &PipelineVariant::default(),
+            self.uniform.descriptor_set_layout,
+        );
+        let lighting_pipeline = self.create_lighting_pipeline(bvk, &lighting);
+        match (geometry_pipeline, lighting_pipeline) {
+            (Some(geometry_pipeline), Some(lighting_pipeline)) => {
+                self.destroy_pipelines(bvk);
+                self.pipelines
+                    .get_or_create(&PipelineVariant::default(), || Some(geometry_pipeline));
+                self.lighting_pipeline = Some(lighting_pipeline);
+                Some(())
+            }
+            (geometry_pipeline, lighting_pipeline) => {
+                geometry_pipeline
+                    .iter()
+                    .chain(&lighting_pipeline)
+                    .for_each(|pipeline| pipeline.destroy(bvk));
+                *self.code_mut(shader)? = old_code;
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub fn has_shader(&self, shader: &str) -> bool {
+        matches!(
+            shader,
+            "vertex" | "gbuffer" | "fullscreen" | "deferred_lighting"
+        )
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn code_mut(&mut self, shader: &str) -> Option<&mut Vec<u32>> {
+        match shader {
+            "vertex" => Some(&mut self.vert_code),
+            "gbuffer" => Some(&mut self.gbuffer_code),
+            "fullscreen" => Some(&mut self.fullscreen_code),
+            "deferred_lighting" => Some(&mut self.lighting_code),
+            _ => None,
+        }
+    }
+
+    fn create_geometry_pipeline(
+        bvk: &BabyVulkan,
+        pass: &PassInfo,
+        vert_code: &[u32],
+        gbuffer_code: &[u32],
+        variant: &PipelineVariant,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Option<VulkanPipeline> {
+        VulkanPipeline::create(
+            bvk,
+            pass,
+            vert_code,
+            gbuffer_code,
+            variant,
+            &[Vertex::bindings()],
+            &Vertex::attributes(),
+            &[descriptor_set_layout],
+        )
+    }
+
+    fn create_lighting_pipeline(
+        &self,
+        bvk: &BabyVulkan,
+        pass: &PassInfo,
+    ) -> Option<VulkanPipeline> {
+        VulkanPipeline::create(
+            bvk,
+            pass,
+            &self.fullscreen_code,
+            &self.lighting_code,
+            &PipelineVariant::default(),
+            &[],
+            &[],
+            &[self.lighting_set_layout],
+        )
+    }
+
+    fn destroy_pipelines(&mut self, bvk: &BabyVulkan) {
+        self.pipelines.clear(bvk);
+        if let Some(pipeline) = self.lighting_pipeline.take() {
+            pipeline.destroy(bvk);
+        }
+    }
+
+    pub fn destroy(&mut self, bvk: &BabyVulkan) {
+        self.destroy_pipelines(bvk);
+        self.destroy_frames(bvk);
+        self.uniform.destroy(bvk);
+        unsafe {
+            bvk.dev
+                .destroy_descriptor_set_layout(self.lighting_set_layout, None);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::*;
+
+//  Destroys something, once nothing on the GPU can be using it anymore.
+pub type Deletion<T> = Box<dyn FnOnce(&mut T)>;
+
+//  Things that were replaced while frames were still in flight.
+//  Whatever is retired goes with the next submission, and is destroyed once that is done: it
+//  was submitted after everything that could still be using them. See `Frames::retire`.
+pub struct DeletionQueue<T = BabyVulkan> {
+    //  Retired since the last submission
+    pending: Vec<Deletion<T>>,
+    //  By the frame whose last submission they went with
+    frames: Vec<Vec<Deletion<T>>>,
+}
+
+impl<T> DeletionQueue<T> {
+    pub fn new(frame_count: usize) -> Self {
+        DeletionQueue {
+            pending: vec![],
+            frames: (0..frame_count).map(|_| vec![]).collect(),
+        }
+    }
+
+    pub fn retire(&mut self, delete: impl FnOnce(&mut T) + 'static) {
+        self.pending.push(Box::new(delete));
+    }
+
+    //  `frame` is about to submit, so everything retired so far waits for that submission.
+    pub fn submit(&mut self, frame: usize) {
+        self.frames[frame].append(&mut self.pending);
+    }
+
+    //  `frame`'s last submission is done.
+    pub fn collect(&mut self, frame: usize, context: &mut T) {
+        for delete in self.frames[frame].drain(..) {
+            delete(context);
+        }
+    }
+
+    //  Everything, for when nothing is in flight anymore.
+    pub fn flush(&mut self, context: &mut T) {
+        for frame in 0..self.frames.len() {
+            self.collect(frame, context);
+        }
+        for delete in self.pending.drain(..) {
+            delete(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deletes_once_the_next_submission_is_done() {
+        let mut deleted: Vec<&'static str> = vec![];
+        let mut queue = DeletionQueue::new(2);
+        queue.retire(|deleted: &mut Vec<_>| deleted.push("old texture"));
+        //  Frame 1's last submission went out before the retire, nothing can go yet
+        queue.collect(1, &mut deleted);
+        assert!(deleted.is_empty());
+
+        queue.submit(0);
+        queue.retire(|deleted: &mut Vec<_>| deleted.push("old mesh"));
+        queue.collect(0, &mut deleted);
+        assert_eq!(deleted, ["old texture"]);
+
+        queue.submit(1);
+        queue.retire(|deleted: &mut Vec<_>| deleted.push("old pipeline"));
+        queue.collect(1, &mut deleted);
+        assert_eq!(deleted, ["old texture", "old mesh"]);
+
+        queue.flush(&mut deleted);
+        assert_eq!(deleted, ["old texture", "old mesh", "old pipeline"]);
+        queue.flush(&mut deleted);
+        assert_eq!(deleted.len(), 3);
+    }
+}
@@ -1,41 +1,127 @@
 use super::*;
 
-pub struct Frames<const N: usize> {
+//  `--frames-in-flight` can go up to this. More only adds latency.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+//  Per frame in flight: what's recorded, the acquire semaphore and what its last submission
+//  signaled. Per swapchain image: the render semaphore that present waits on, and what the last
+//  frame drawing into it signaled. There can be more images than frames, and they don't come in
+//  order.
+pub struct Frames {
     current_frame: usize,
-    pub cmd_bufs: [vk::CommandBuffer; N],
-    pub render_semaphores: [vk::Semaphore; N],
-    pub present_semaphores: [vk::Semaphore; N],
-    pub frame_fences: [vk::Fence; N],
+    cmd_pool: vk::CommandPool,
+    pub cmd_bufs: Vec<vk::CommandBuffer>,
+    pub present_semaphores: Vec<vk::Semaphore>,
+    //  Only signaled without a timeline, see `GpuWait`
+    frame_fences: Vec<vk::Fence>,
+    frame_waits: Vec<Option<GpuWait>>,
+    pub render_semaphores: Vec<vk::Semaphore>,
+    images_in_flight: Vec<Option<GpuWait>>,
+    deletions: DeletionQueue,
 }
 
-impl<const N: usize> Frames<N> {
-    pub fn create(bvk: &BabyVulkan, cmd_pool: vk::CommandPool) -> Option<Self> {
+impl Frames {
+    pub fn create(
+        bvk: &BabyVulkan,
+        cmd_pool: vk::CommandPool,
+        frame_count: usize,
+        image_count: usize,
+    ) -> Option<Self> {
         let mut frames = Frames {
             current_frame: 0,
-            cmd_bufs: [vk::CommandBuffer::null(); N],
-            render_semaphores: [vk::Semaphore::null(); N],
-            present_semaphores: [vk::Semaphore::null(); N],
-            frame_fences: [vk::Fence::null(); N],
+            cmd_pool,
+            cmd_bufs: vec![],
+            present_semaphores: vec![],
+            frame_fences: vec![],
+            frame_waits: vec![None; frame_count],
+            render_semaphores: vec![],
+            images_in_flight: vec![],
+            deletions: DeletionQueue::new(frame_count),
         };
 
-        for i in 0..N {
-            frames.cmd_bufs[i] = bvk.create_primary_command_buffer(cmd_pool)?;
-            frames.render_semaphores[i] = bvk.create_semaphore()?;
-            frames.present_semaphores[i] = bvk.create_semaphore()?;
-            frames.frame_fences[i] = bvk.create_fence(true)?;
+        for _ in 0..frame_count {
+            frames
+                .cmd_bufs
+                .push(bvk.create_primary_command_buffer(cmd_pool)?);
+            frames.present_semaphores.push(bvk.create_semaphore()?);
+            frames.frame_fences.push(bvk.create_fence(false)?);
         }
+        frames.set_image_count(bvk, image_count)?;
 
         Some(frames)
     }
 
-    pub fn destroy(&self, bvk: &BabyVulkan) {
-        for i in 0..N {
-            unsafe {
-                bvk.dev.destroy_semaphore(self.render_semaphores[i], None);
-                bvk.dev.destroy_semaphore(self.present_semaphores[i], None);
-                bvk.dev.destroy_fence(self.frame_fences[i], None);
+    //  For a new swapchain. Nothing may be in flight, but presenting the old images may still
+    //  wait on their render semaphores, so those are retired until the next frame is done.
+    pub fn set_image_count(&mut self, bvk: &BabyVulkan, image_count: usize) -> Option<()> {
+        let old: Vec<_> = self.render_semaphores.drain(..).collect();
+        self.retire(move |bvk| {
+            for semaphore in old {
+                unsafe { bvk.dev.destroy_semaphore(semaphore, None) };
+            }
+        });
+        self.images_in_flight = vec![None; image_count];
+        for _ in 0..image_count {
+            self.render_semaphores.push(bvk.create_semaphore()?);
+        }
+        Some(())
+    }
+
+    //  Wait until the current frame's last submission is done, so that what it used can be
+    //  used again, and what was retired before it can be destroyed.
+    pub fn wait_for_frame(&mut self, bvk: &mut BabyVulkan) -> Option<()> {
+        if let Some(done) = self.frame_waits[self.current_frame] {
+            done.wait(bvk)?;
+        }
+        self.deletions.collect(self.current_frame, bvk);
+        Some(())
+    }
+
+    //  Destroy something with `delete` once no frame in flight can be using it, instead of
+    //  waiting for the device to go idle. That's after the next submission, see `begin_submit`.
+    pub fn retire(&mut self, delete: impl FnOnce(&mut BabyVulkan) + 'static) {
+        self.deletions.retire(delete);
+    }
+
+    //  Wait until no earlier frame is still drawing into `image`, then hand it to this one.
+    //  Returns what this frame's submission has to signal, which everything retired so far now
+    //  waits for.
+    //  Has to be followed by that submission, or waiting for this frame never ends.
+    pub fn begin_submit(&mut self, bvk: &BabyVulkan, image: usize) -> Option<GpuWait> {
+        let current = self.frame_waits[self.current_frame];
+        if let Some(previous) = self.images_in_flight[image] {
+            if Some(previous) != current {
+                previous.wait(bvk)?;
             }
         }
+        let done = GpuWait::next(bvk, self.frame_fences[self.current_frame])?;
+        self.frame_waits[self.current_frame] = Some(done);
+        self.images_in_flight[image] = Some(done);
+        self.deletions.submit(self.current_frame);
+        Some(done)
+    }
+
+    //  Nothing may be in flight.
+    pub fn destroy(&mut self, bvk: &mut BabyVulkan) {
+        self.deletions.flush(bvk);
+        unsafe {
+            bvk.dev.free_command_buffers(self.cmd_pool, &self.cmd_bufs);
+            for (&semaphore, &fence) in self.present_semaphores.iter().zip(&self.frame_fences) {
+                bvk.dev.destroy_semaphore(semaphore, None);
+                bvk.dev.destroy_fence(fence, None);
+            }
+        }
+        self.destroy_image_semaphores(bvk);
+    }
+
+    fn destroy_image_semaphores(&mut self, bvk: &BabyVulkan) {
+        for semaphore in self.render_semaphores.drain(..) {
+            unsafe { bvk.dev.destroy_semaphore(semaphore, None) };
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.cmd_bufs.len()
     }
 
     pub fn get_current_frame(&self) -> usize {
@@ -43,6 +129,6 @@ impl<const N: usize> Frames<N> {
     }
 
     pub fn advance(&mut self) {
-        self.current_frame = (self.current_frame + 1) % N
+        self.current_frame = (self.current_frame + 1) % self.frame_count()
     }
 }
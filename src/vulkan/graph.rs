@@ -0,0 +1,1820 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+//  A frame, described as passes and the images and buffers they use.
+//  It is built again every frame, then `GraphCache::compile` works out the order of the passes,
+//  the barriers between them, and the images, render passes and framebuffers they need.
+//  With dynamic rendering, the attachments are given when recording instead.
+//  Passes with subpasses always get a render pass, later subpasses read the earlier ones' attachments
+//  as input attachments without them ever leaving the pass.
+//
+//  Every write gives back a new handle for the new contents. Passes run in whatever order gets
+//  every one of them the contents it asked for, and passes whose results are never used
+//  (see `keep_image` and `present`) are dropped.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PassHandle(usize);
+
+//  The contents of an image between two writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageHandle {
+    idx: usize,
+    version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle {
+    idx: usize,
+    version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Resource {
+    Image(usize),
+    Buffer(usize),
+}
+
+//  What an image looks like. Transient images with the same description share memory when
+//  their passes don't overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub samples: vk::SampleCountFlags,
+    pub usage: vk::ImageUsageFlags,
+}
+
+impl ImageDesc {
+    //  Drawn into, then sampled. Multisampled ones are only ever resolved.
+    pub fn color(format: vk::Format, extent: vk::Extent2D, samples: vk::SampleCountFlags) -> Self {
+        Self::attachment(
+            format,
+            extent,
+            samples,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        )
+    }
+
+    pub fn depth(format: vk::Format, extent: vk::Extent2D, samples: vk::SampleCountFlags) -> Self {
+        Self::attachment(
+            format,
+            extent,
+            samples,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+
+    //  Drawn into and read by a later subpass of the same pass, e.g. a G-buffer.
+    //  It never leaves the render pass, so it doesn't need memory of its own on tiled GPUs.
+    pub fn subpass_input(format: vk::Format, extent: vk::Extent2D) -> Self {
+        let usage = if is_depth(format) {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+        ImageDesc {
+            format,
+            extent,
+            samples: vk::SampleCountFlags::TYPE_1,
+            usage: usage
+                | vk::ImageUsageFlags::INPUT_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+        }
+    }
+
+    fn attachment(
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        ImageDesc {
+            format,
+            extent,
+            samples,
+            usage: if samples == vk::SampleCountFlags::TYPE_1 {
+                usage | vk::ImageUsageFlags::SAMPLED
+            } else {
+                usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT
+            },
+        }
+    }
+}
+
+//  An image the graph doesn't own, e.g. a texture or a swapchain image.
+#[derive(Clone, Copy)]
+pub struct ImportedImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub desc: ImageDesc,
+    //  Where the image is at when the frame starts, if not where the last frame left it.
+    //  Swapchain images come back from the presentation engine, for example.
+    pub initial: Option<SyncState>,
+}
+
+impl ImportedImage {
+    pub fn texture(texture: &Texture, extent: vk::Extent2D) -> Self {
+        ImportedImage {
+            image: texture.image.image,
+            view: texture.image_view,
+            desc: ImageDesc {
+                format: texture.image.format,
+                extent,
+                samples: vk::SampleCountFlags::TYPE_1,
+                usage: vk::ImageUsageFlags::empty(),
+            },
+            initial: None,
+        }
+    }
+}
+
+enum ImageSource {
+    Transient(ImageDesc),
+    Imported(ImportedImage),
+}
+
+struct GraphImage {
+    name: &'static str,
+    source: ImageSource,
+    //  The version the next write has to start from
+    latest: u32,
+    //  Where the image has to be left at the end of the frame
+    final_layout: Option<vk::ImageLayout>,
+}
+
+impl GraphImage {
+    fn desc(&self) -> ImageDesc {
+        match &self.source {
+            ImageSource::Transient(desc) => *desc,
+            ImageSource::Imported(imported) => imported.desc,
+        }
+    }
+}
+
+struct GraphBuffer {
+    name: &'static str,
+    buffer: vk::Buffer,
+    latest: u32,
+}
+
+//  What happens to an attachment when its pass starts
+#[derive(Clone, Copy)]
+pub enum LoadOp {
+    Clear(vk::ClearValue),
+    //  Nothing draws on top of an earlier pass yet
+    #[allow(dead_code)]
+    Load,
+    DontCare,
+}
+
+impl LoadOp {
+    fn op(&self) -> (vk::AttachmentLoadOp, vk::ClearValue) {
+        match self {
+            LoadOp::Clear(value) => (vk::AttachmentLoadOp::CLEAR, *value),
+            LoadOp::Load => (vk::AttachmentLoadOp::LOAD, vk::ClearValue::default()),
+            LoadOp::DontCare => (vk::AttachmentLoadOp::DONT_CARE, vk::ClearValue::default()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Attachment {
+    Color(LoadOp),
+    Depth(LoadOp),
+    //  Resolves the color attachment with the same index
+    Resolve,
+    //  Something an earlier subpass drew, read from the same pixel
+    Input,
+}
+
+struct PassAccess {
+    resource: Resource,
+    //  The version the pass sees. Writing makes `version + 1`.
+    version: u32,
+    //  Whether the pass cares about the contents of `version`, or just draws over them
+    reads: bool,
+    writes: bool,
+    stages: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+    layout: vk::ImageLayout,
+    attachment: Option<Attachment>,
+    subpass: u32,
+}
+
+impl PassAccess {
+    fn sync(&self) -> Access {
+        if self.writes {
+            Access::write(self.stages, self.access, self.layout, !self.reads)
+        } else {
+            Access::read(self.stages, self.access, self.layout)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassKind {
+    //  Runs inside a render pass made from its attachments
+    Graphics,
+    Compute,
+}
+
+struct GraphPass {
+    name: &'static str,
+    kind: PassKind,
+    accesses: Vec<PassAccess>,
+    stencil_ops: Option<StencilOps>,
+    subpasses: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphError {
+    StaleWrite {
+        pass: &'static str,
+        resource: &'static str,
+    },
+    Uninitialized {
+        pass: &'static str,
+        resource: &'static str,
+    },
+    BadAttachments {
+        pass: &'static str,
+        why: &'static str,
+    },
+    Cycle,
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::StaleWrite { pass, resource } => write!(
+                f,
+                "Pass {} writes an old version of {}, something else already wrote over it",
+                pass, resource
+            ),
+            GraphError::Uninitialized { pass, resource } => write!(
+                f,
+                "Pass {} reads {} before anything wrote it",
+                pass, resource
+            ),
+            GraphError::BadAttachments { pass, why } => {
+                write!(f, "Pass {} has bad attachments: {}", pass, why)
+            }
+            GraphError::Cycle => write!(f, "Passes depend on each other in a cycle"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RenderGraph {
+    images: Vec<GraphImage>,
+    buffers: Vec<GraphBuffer>,
+    passes: Vec<GraphPass>,
+    //  Versions that are needed after the frame
+    kept: Vec<(Resource, u32)>,
+    //  Found while adding passes, reported by `plan`
+    errors: Vec<GraphError>,
+}
+
+impl RenderGraph {
+    //  An image that only lives for this frame. The graph finds memory for it.
+    pub fn create_image(&mut self, name: &'static str, desc: ImageDesc) -> ImageHandle {
+        self.add_image(name, ImageSource::Transient(desc))
+    }
+
+    pub fn import_image(&mut self, name: &'static str, image: ImportedImage) -> ImageHandle {
+        self.add_image(name, ImageSource::Imported(image))
+    }
+
+    pub fn import_buffer(&mut self, name: &'static str, buffer: vk::Buffer) -> BufferHandle {
+        self.buffers.push(GraphBuffer {
+            name,
+            buffer,
+            latest: 0,
+        });
+        BufferHandle {
+            idx: self.buffers.len() - 1,
+            version: 0,
+        }
+    }
+
+    pub fn add_pass(&mut self, name: &'static str, kind: PassKind) -> PassBuilder<'_> {
+        self.passes.push(GraphPass {
+            name,
+            kind,
+            accesses: vec![],
+            stencil_ops: None,
+            subpasses: 1,
+        });
+        PassBuilder {
+            pass: self.passes.len() - 1,
+            graph: self,
+        }
+    }
+
+    //  Whatever writes `image` runs even though no pass reads it
+    pub fn keep_image(&mut self, image: ImageHandle) {
+        self.kept.push((Resource::Image(image.idx), image.version));
+    }
+
+    //  Same as `keep_image`, and leaves it ready to be presented
+    pub fn present(&mut self, image: ImageHandle) {
+        self.keep_image(image);
+        self.images[image.idx].final_layout = Some(vk::ImageLayout::PRESENT_SRC_KHR);
+    }
+
+    fn add_image(&mut self, name: &'static str, source: ImageSource) -> ImageHandle {
+        self.images.push(GraphImage {
+            name,
+            source,
+            latest: 0,
+            final_layout: None,
+        });
+        ImageHandle {
+            idx: self.images.len() - 1,
+            version: 0,
+        }
+    }
+
+    fn resource_name(&self, resource: Resource) -> &'static str {
+        match resource {
+            Resource::Image(idx) => self.images[idx].name,
+            Resource::Buffer(idx) => self.buffers[idx].name,
+        }
+    }
+
+    //  Works out which passes run, in what order, and where the transient images go.
+    pub fn plan(&self) -> Result<GraphPlan, GraphError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+
+        //  Who wrote which version, and who only read it
+        let mut writers: HashMap<(Resource, u32), usize> = HashMap::new();
+        let mut readers: HashMap<(Resource, u32), Vec<usize>> = HashMap::new();
+        for (p, pass) in self.passes.iter().enumerate() {
+            self.check_attachments(pass)?;
+            for access in &pass.accesses {
+                if access.writes {
+                    writers.insert((access.resource, access.version + 1), p);
+                } else {
+                    readers
+                        .entry((access.resource, access.version))
+                        .or_default()
+                        .push(p);
+                }
+                let transient = matches!(
+                    access.resource,
+                    Resource::Image(idx) if matches!(self.images[idx].source, ImageSource::Transient(_))
+                );
+                if access.reads && access.version == 0 && transient {
+                    return Err(GraphError::Uninitialized {
+                        pass: pass.name,
+                        resource: self.resource_name(access.resource),
+                    });
+                }
+            }
+        }
+
+        //  `deps` is everything that has to run first, `inputs` only what made the contents we use
+        let mut deps: Vec<HashSet<usize>> = vec![HashSet::new(); self.passes.len()];
+        let mut inputs: Vec<Vec<usize>> = vec![vec![]; self.passes.len()];
+        for (p, pass) in self.passes.iter().enumerate() {
+            for access in &pass.accesses {
+                let key = (access.resource, access.version);
+                if let Some(&writer) = writers.get(&key).filter(|&&writer| writer != p) {
+                    deps[p].insert(writer);
+                    if access.reads {
+                        inputs[p].push(writer);
+                    }
+                }
+                //  Don't write over something before everyone had a look at it
+                if access.writes {
+                    for &reader in readers.get(&key).into_iter().flatten() {
+                        if reader != p {
+                            deps[p].insert(reader);
+                        }
+                    }
+                }
+            }
+        }
+
+        //  Cull everything that doesn't lead to a kept version
+        let mut alive = vec![false; self.passes.len()];
+        let mut stack: Vec<usize> = self
+            .kept
+            .iter()
+            .filter_map(|kept| writers.get(kept).copied())
+            .collect();
+        while let Some(p) = stack.pop() {
+            if !alive[p] {
+                alive[p] = true;
+                stack.extend(&inputs[p]);
+            }
+        }
+
+        //  Order what's left, keeping to the order passes were added in where possible
+        let alive_count = alive.iter().filter(|&&alive| alive).count();
+        let mut placed = vec![false; self.passes.len()];
+        let mut order = Vec::with_capacity(alive_count);
+        while order.len() < alive_count {
+            let next = (0..self.passes.len()).find(|&p| {
+                alive[p] && !placed[p] && deps[p].iter().all(|&dep| placed[dep] || !alive[dep])
+            });
+            match next {
+                Some(p) => {
+                    placed[p] = true;
+                    order.push(p);
+                }
+                None => return Err(GraphError::Cycle),
+            }
+        }
+
+        //  Versions somebody looks at have to be stored by their pass
+        let mut needed: HashSet<(Resource, u32)> = self.kept.iter().copied().collect();
+        //  Input attachments are read before the pass ends, so they don't count.
+        for &p in &order {
+            for access in self.passes[p].accesses.iter().filter(|access| {
+                access.reads && !matches!(access.attachment, Some(Attachment::Input))
+            }) {
+                needed.insert((access.resource, access.version));
+            }
+        }
+
+        Ok(GraphPlan {
+            slots: self.allocate_transients(&order),
+            order,
+            needed,
+        })
+    }
+
+    //  Transient images that are never used at the same time can share memory.
+    //  A slot is one image in the pool for that description.
+    fn allocate_transients(&self, order: &[usize]) -> Vec<Option<usize>> {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.images.len()];
+        for (position, &p) in order.iter().enumerate() {
+            for access in &self.passes[p].accesses {
+                if let Resource::Image(idx) = access.resource {
+                    lifetimes[idx] = Some(match lifetimes[idx] {
+                        Some((first, _)) => (first, position),
+                        None => (position, position),
+                    });
+                }
+            }
+        }
+        let mut transients: Vec<(usize, usize, usize, ImageDesc)> = self
+            .images
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, image)| match (&image.source, lifetimes[idx]) {
+                (ImageSource::Transient(desc), Some((first, last))) => {
+                    Some((first, last, idx, *desc))
+                }
+                _ => None,
+            })
+            .collect();
+        transients.sort_by_key(|&(first, _, idx, _)| (first, idx));
+
+        //  When each slot is used for the last time so far
+        let mut pools: HashMap<ImageDesc, Vec<usize>> = HashMap::new();
+        let mut slots = vec![None; self.images.len()];
+        for (first, last, idx, desc) in transients {
+            let pool = pools.entry(desc).or_default();
+            let slot = match pool.iter().position(|&end| end < first) {
+                Some(slot) => slot,
+                None => {
+                    pool.push(0);
+                    pool.len() - 1
+                }
+            };
+            pool[slot] = last;
+            slots[idx] = Some(slot);
+        }
+        slots
+    }
+
+    fn check_attachments(&self, pass: &GraphPass) -> Result<(), GraphError> {
+        let bad = |why| {
+            Err(GraphError::BadAttachments {
+                pass: pass.name,
+                why,
+            })
+        };
+        let attachments: Vec<(&PassAccess, Attachment, ImageDesc)> = pass
+            .accesses
+            .iter()
+            .filter_map(|access| match (access.attachment, access.resource) {
+                (Some(attachment), Resource::Image(idx)) => {
+                    Some((access, attachment, self.images[idx].desc()))
+                }
+                _ => None,
+            })
+            .collect();
+        match pass.kind {
+            PassKind::Compute if !attachments.is_empty() => bad("compute passes can't have any"),
+            PassKind::Compute => Ok(()),
+            PassKind::Graphics if attachments.is_empty() => bad("there are none"),
+            PassKind::Graphics => {
+                let extent = attachments[0].2.extent;
+                if attachments.iter().any(|(_, _, desc)| desc.extent != extent) {
+                    return bad("they are not all the same size");
+                }
+                for subpass in 0..pass.subpasses {
+                    let count = |matches: fn(&Attachment) -> bool| {
+                        attachments
+                            .iter()
+                            .filter(|(access, attachment, _)| {
+                                access.subpass == subpass && matches(attachment)
+                            })
+                            .count()
+                    };
+                    let colors = count(|attachment| matches!(attachment, Attachment::Color(_)));
+                    let depths = count(|attachment| matches!(attachment, Attachment::Depth(_)));
+                    let resolves = count(|attachment| matches!(attachment, Attachment::Resolve));
+                    let inputs = count(|attachment| matches!(attachment, Attachment::Input));
+                    if colors + depths + inputs == 0 {
+                        return bad("a subpass has none");
+                    } else if depths > 1 {
+                        return bad("there is more than one depth attachment");
+                    } else if resolves != 0 && resolves != colors {
+                        return bad("either every color attachment is resolved or none is");
+                    }
+                }
+                //  Inputs have to be drawn by an earlier subpass of the same pass
+                let drawn_earlier = |input: &PassAccess| {
+                    attachments.iter().any(|(access, attachment, _)| {
+                        access.resource == input.resource
+                            && access.subpass < input.subpass
+                            && !matches!(attachment, Attachment::Input)
+                    })
+                };
+                if attachments.iter().any(|(access, attachment, _)| {
+                    matches!(attachment, Attachment::Input) && !drawn_earlier(access)
+                }) {
+                    bad("input attachments have to be drawn by an earlier subpass")
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+//  Adds what a pass uses, see `RenderGraph::add_pass`.
+//  Everything that writes returns the handle for the new contents.
+pub struct PassBuilder<'a> {
+    graph: &'a mut RenderGraph,
+    pass: usize,
+}
+
+impl PassBuilder<'_> {
+    pub fn handle(&self) -> PassHandle {
+        PassHandle(self.pass)
+    }
+
+    pub fn color(&mut self, image: ImageHandle, load: LoadOp) -> ImageHandle {
+        let reads = matches!(load, LoadOp::Load);
+        let access = vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | if reads {
+                vk::AccessFlags::COLOR_ATTACHMENT_READ
+            } else {
+                vk::AccessFlags::empty()
+            };
+        self.write_image(
+            image,
+            reads,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            access,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            Some(Attachment::Color(load)),
+        )
+    }
+
+    //  Resolves the color attachment added in the same order
+    pub fn resolve(&mut self, image: ImageHandle) -> ImageHandle {
+        self.write_image(
+            image,
+            false,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            Some(Attachment::Resolve),
+        )
+    }
+
+    pub fn depth(&mut self, image: ImageHandle, load: LoadOp) -> ImageHandle {
+        self.write_image(
+            image,
+            matches!(load, LoadOp::Load),
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            Some(Attachment::Depth(load)),
+        )
+    }
+
+    //  By default, the stencil is loaded and stored along with the depth.
+    pub fn stencil_ops(&mut self, stencil_ops: StencilOps) {
+        self.graph.passes[self.pass].stencil_ops = Some(stencil_ops);
+    }
+
+    //  Whatever is added after this goes into a new subpass.
+    //  The pass moves on to it by itself when recording, see `CompiledGraph::execute`.
+    pub fn next_subpass(&mut self) {
+        self.graph.passes[self.pass].subpasses += 1;
+    }
+
+    //  Reads what an earlier subpass drew into `image`, see `ImageDesc::subpass_input`
+    pub fn input(&mut self, image: ImageHandle) {
+        let layout = if is_depth(self.graph.images[image.idx].desc().format) {
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        } else {
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        };
+        self.add(PassAccess {
+            resource: Resource::Image(image.idx),
+            version: image.version,
+            reads: true,
+            writes: false,
+            stages: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            access: vk::AccessFlags::INPUT_ATTACHMENT_READ,
+            layout,
+            attachment: Some(Attachment::Input),
+            subpass: self.subpass(),
+        });
+    }
+
+    pub fn sample(&mut self, image: ImageHandle, stages: vk::PipelineStageFlags) {
+        self.add(PassAccess {
+            resource: Resource::Image(image.idx),
+            version: image.version,
+            reads: true,
+            writes: false,
+            stages,
+            access: vk::AccessFlags::SHADER_READ,
+            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            attachment: None,
+            subpass: self.subpass(),
+        });
+    }
+
+    //  Every pixel gets written, so the old contents are thrown away
+    pub fn write_storage_image(
+        &mut self,
+        image: ImageHandle,
+        stages: vk::PipelineStageFlags,
+    ) -> ImageHandle {
+        self.write_image(
+            image,
+            false,
+            stages,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::ImageLayout::GENERAL,
+            None,
+        )
+    }
+
+    pub fn vertex_buffer(&mut self, buffer: BufferHandle) {
+        self.read_buffer(
+            buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+        );
+    }
+
+    pub fn read_storage_buffer(&mut self, buffer: BufferHandle, stages: vk::PipelineStageFlags) {
+        self.read_buffer(buffer, stages, vk::AccessFlags::SHADER_READ);
+    }
+
+    pub fn write_storage_buffer(
+        &mut self,
+        buffer: BufferHandle,
+        stages: vk::PipelineStageFlags,
+    ) -> BufferHandle {
+        let graph_buffer = &mut self.graph.buffers[buffer.idx];
+        let stale = buffer.version != graph_buffer.latest;
+        graph_buffer.latest += 1;
+        let latest = graph_buffer.latest;
+        self.check_stale(stale, Resource::Buffer(buffer.idx));
+        self.add(PassAccess {
+            resource: Resource::Buffer(buffer.idx),
+            version: buffer.version,
+            reads: false,
+            writes: true,
+            stages,
+            access: vk::AccessFlags::SHADER_WRITE,
+            layout: vk::ImageLayout::UNDEFINED,
+            attachment: None,
+            subpass: self.subpass(),
+        });
+        BufferHandle {
+            idx: buffer.idx,
+            version: latest,
+        }
+    }
+
+    fn read_buffer(
+        &mut self,
+        buffer: BufferHandle,
+        stages: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+    ) {
+        self.add(PassAccess {
+            resource: Resource::Buffer(buffer.idx),
+            version: buffer.version,
+            reads: true,
+            writes: false,
+            stages,
+            access,
+            layout: vk::ImageLayout::UNDEFINED,
+            attachment: None,
+            subpass: self.subpass(),
+        });
+    }
+
+    fn write_image(
+        &mut self,
+        image: ImageHandle,
+        reads: bool,
+        stages: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+        layout: vk::ImageLayout,
+        attachment: Option<Attachment>,
+    ) -> ImageHandle {
+        let graph_image = &mut self.graph.images[image.idx];
+        let stale = image.version != graph_image.latest;
+        graph_image.latest += 1;
+        let latest = graph_image.latest;
+        self.check_stale(stale, Resource::Image(image.idx));
+        self.add(PassAccess {
+            resource: Resource::Image(image.idx),
+            version: image.version,
+            reads,
+            writes: true,
+            stages,
+            access,
+            layout,
+            attachment,
+            subpass: self.subpass(),
+        });
+        ImageHandle {
+            idx: image.idx,
+            version: latest,
+        }
+    }
+
+    fn subpass(&self) -> u32 {
+        self.graph.passes[self.pass].subpasses - 1
+    }
+
+    fn check_stale(&mut self, stale: bool, resource: Resource) {
+        if stale {
+            let error = GraphError::StaleWrite {
+                pass: self.graph.passes[self.pass].name,
+                resource: self.graph.resource_name(resource),
+            };
+            self.graph.errors.push(error);
+        }
+    }
+
+    fn add(&mut self, access: PassAccess) {
+        self.graph.passes[self.pass].accesses.push(access);
+    }
+}
+
+pub struct GraphPlan {
+    //  The passes that run, in order
+    order: Vec<usize>,
+    //  For transient images, which image of the pool with the same description they get
+    slots: Vec<Option<usize>>,
+    needed: HashSet<(Resource, u32)>,
+}
+
+impl GraphPlan {
+    //  Whether what `access` writes is looked at later
+    fn stores(&self, access: &PassAccess) -> bool {
+        access.writes && self.needed.contains(&(access.resource, access.version + 1))
+    }
+}
+
+//  The attachments of a pass, as far as pipelines care.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PassLayout {
+    pub colors: Vec<vk::Format>,
+    pub depth: Option<vk::Format>,
+    pub samples: vk::SampleCountFlags,
+    //  Every color attachment is resolved
+    pub resolve: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct AttachmentKey {
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+    stencil_load_op: vk::AttachmentLoadOp,
+    stencil_store_op: vk::AttachmentStoreOp,
+    layout: vk::ImageLayout,
+}
+
+//  Attachments of one subpass, as indices into `RenderPassKey::attachments`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct SubpassKey {
+    colors: Vec<u32>,
+    depth: Option<u32>,
+    resolves: Vec<u32>,
+    //  With the layout they are read in
+    inputs: Vec<(u32, vk::ImageLayout)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RenderPassKey {
+    //  Subpass by subpass, the colors, then the depth, then the resolves of each
+    attachments: Vec<AttachmentKey>,
+    subpasses: Vec<SubpassKey>,
+}
+
+impl RenderPassKey {
+    //  Pipelines only care about formats and samples, so any ops will do.
+    fn compatible(layout: &PassLayout) -> Self {
+        let key = |format, samples, store_op| AttachmentKey {
+            format,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let mut attachments: Vec<AttachmentKey> = layout
+            .colors
+            .iter()
+            .map(|&format| key(format, layout.samples, vk::AttachmentStoreOp::STORE))
+            .collect();
+        let mut subpass = SubpassKey {
+            colors: (0..attachments.len() as u32).collect(),
+            ..Default::default()
+        };
+        if let Some(format) = layout.depth {
+            subpass.depth = Some(attachments.len() as u32);
+            attachments.push(AttachmentKey {
+                layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..key(format, layout.samples, vk::AttachmentStoreOp::DONT_CARE)
+            });
+        }
+        if layout.resolve {
+            for &format in &layout.colors {
+                subpass.resolves.push(attachments.len() as u32);
+                attachments.push(key(
+                    format,
+                    vk::SampleCountFlags::TYPE_1,
+                    vk::AttachmentStoreOp::STORE,
+                ));
+            }
+        }
+        RenderPassKey {
+            attachments,
+            subpasses: vec![subpass],
+        }
+    }
+
+    //  What pipelines in each subpass are built against
+    fn layouts(&self) -> Vec<PassLayout> {
+        self.subpasses
+            .iter()
+            .map(|subpass| {
+                let format = |&idx: &u32| self.attachments[idx as usize].format;
+                PassLayout {
+                    colors: subpass.colors.iter().map(format).collect(),
+                    depth: subpass.depth.as_ref().map(format),
+                    samples: subpass
+                        .colors
+                        .iter()
+                        .chain(&subpass.depth)
+                        .next()
+                        .map(|&idx| self.attachments[idx as usize].samples)
+                        .unwrap_or(vk::SampleCountFlags::TYPE_1),
+                    resolve: !subpass.resolves.is_empty(),
+                }
+            })
+            .collect()
+    }
+
+    fn create_render_pass(&self, bvk: &BabyVulkan) -> Option<vk::RenderPass> {
+        //  Outside of the pass, layouts never change. The graph's barriers take care of that.
+        let attachments: Vec<vk::AttachmentDescription> = self
+            .attachments
+            .iter()
+            .map(|key| {
+                vk::AttachmentDescription::builder()
+                    .format(key.format)
+                    .samples(key.samples)
+                    .load_op(key.load_op)
+                    .store_op(key.store_op)
+                    .stencil_load_op(key.stencil_load_op)
+                    .stencil_store_op(key.stencil_store_op)
+                    .initial_layout(key.layout)
+                    .final_layout(key.layout)
+                    .build()
+            })
+            .collect();
+        let reference = |attachment: u32, layout: vk::ImageLayout| {
+            vk::AttachmentReference::builder()
+                .attachment(attachment)
+                .layout(layout)
+                .build()
+        };
+        let in_place =
+            |&attachment: &u32| reference(attachment, self.attachments[attachment as usize].layout);
+
+        //  Create Render Pass Subpasses
+        //  (Colors, Depth, Resolves, Inputs), kept alive for the descriptions pointing at them
+        let references: Vec<_> = self
+            .subpasses
+            .iter()
+            .map(|subpass| {
+                (
+                    subpass.colors.iter().map(in_place).collect::<Vec<_>>(),
+                    subpass.depth.as_ref().map(in_place),
+                    subpass.resolves.iter().map(in_place).collect::<Vec<_>>(),
+                    subpass
+                        .inputs
+                        .iter()
+                        .map(|&(attachment, layout)| reference(attachment, layout))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let subpasses: Vec<vk::SubpassDescription> = references
+            .iter()
+            .map(|(colors, depth, resolves, inputs)| {
+                let mut subpass = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(colors)
+                    .input_attachments(inputs);
+                if let Some(depth) = depth {
+                    subpass = subpass.depth_stencil_attachment(depth);
+                }
+                if !resolves.is_empty() {
+                    subpass = subpass.resolve_attachments(resolves);
+                }
+                subpass.build()
+            })
+            .collect();
+
+        //  Create Render Pass Dependencies
+        //  Only between subpasses, there are barriers around the pass instead.
+        let mut dependencies: Vec<vk::SubpassDependency> = vec![];
+        for (dst, subpass) in self.subpasses.iter().enumerate() {
+            for &(input, _) in &subpass.inputs {
+                let src = self.subpasses.iter().position(|earlier| {
+                    earlier.colors.contains(&input) || earlier.depth == Some(input)
+                })?;
+                if dependencies.iter().any(|dependency| {
+                    dependency.src_subpass == src as u32 && dependency.dst_subpass == dst as u32
+                }) {
+                    continue;
+                }
+                dependencies.push(
+                    vk::SubpassDependency::builder()
+                        .src_subpass(src as u32)
+                        .dst_subpass(dst as u32)
+                        .src_stage_mask(
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                        )
+                        .src_access_mask(
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                        )
+                        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                        .dst_access_mask(vk::AccessFlags::INPUT_ATTACHMENT_READ)
+                        //  Every pixel only reads itself
+                        .dependency_flags(vk::DependencyFlags::BY_REGION)
+                        .build(),
+                );
+            }
+        }
+
+        //  Create Render Pass
+        let render_pass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies)
+            .build();
+        unsafe { bvk.dev.create_render_pass(&render_pass_info, None) }.ok()
+    }
+}
+
+//  Everything the graph creates, kept around between frames so that it is only created once.
+//  Also remembers where every image and buffer was left, so that the next frame can wait for it.
+#[derive(Default)]
+pub struct GraphCache {
+    images: HashMap<ImageDesc, Vec<(Image, vk::ImageView)>>,
+    render_passes: HashMap<RenderPassKey, vk::RenderPass>,
+    framebuffers: HashMap<(vk::RenderPass, Vec<vk::ImageView>, vk::Extent2D), vk::Framebuffer>,
+    image_states: HashMap<vk::Image, SyncState>,
+    buffer_states: HashMap<vk::Buffer, SyncState>,
+}
+
+impl GraphCache {
+    //  Plans `graph` and gets everything ready to record it.
+    //  This assumes the result is recorded and submitted, in the order `compile` was called.
+    pub fn compile(&mut self, bvk: &BabyVulkan, graph: &RenderGraph) -> Option<CompiledGraph> {
+        let plan = graph.plan().map_err(|e| println!("[Graph] {}", e)).ok()?;
+
+        //  Find the actual images
+        let mut physical: Vec<Option<(vk::Image, vk::ImageView)>> = vec![];
+        for (idx, image) in graph.images.iter().enumerate() {
+            physical.push(match (&image.source, plan.slots[idx]) {
+                (ImageSource::Imported(imported), _) => {
+                    if let Some(initial) = imported.initial {
+                        self.image_states.insert(imported.image, initial);
+                    }
+                    Some((imported.image, imported.view))
+                }
+                (ImageSource::Transient(desc), Some(slot)) => {
+                    Some(self.get_transient(bvk, desc, slot)?)
+                }
+                //  Not used by anything that runs
+                (ImageSource::Transient(_), None) => None,
+            });
+        }
+
+        let mut steps = Vec::with_capacity(plan.order.len());
+        for &p in &plan.order {
+            let pass = &graph.passes[p];
+            let mut barriers = Barriers::default();
+            //  Input attachments are taken care of by the render pass' own dependencies
+            for access in pass
+                .accesses
+                .iter()
+                .filter(|access| !matches!(access.attachment, Some(Attachment::Input)))
+            {
+                match access.resource {
+                    Resource::Image(idx) => {
+                        let (image, _) = physical[idx]?;
+                        let state = self
+                            .image_states
+                            .entry(image)
+                            .or_insert_with(|| SyncState::new(vk::ImageLayout::UNDEFINED));
+                        if let Some(barrier) = state.access(access.sync()) {
+                            let aspect = format_aspect(graph.images[idx].desc().format);
+                            barriers.image(image, aspect, barrier);
+                        }
+                    }
+                    Resource::Buffer(idx) => {
+                        let buffer = graph.buffers[idx].buffer;
+                        let state = self
+                            .buffer_states
+                            .entry(buffer)
+                            .or_insert_with(|| SyncState::new(vk::ImageLayout::UNDEFINED));
+                        if let Some(barrier) = state.access(access.sync()) {
+                            barriers.buffer(buffer, barrier);
+                        }
+                    }
+                }
+            }
+            let render = match pass.kind {
+                PassKind::Graphics => {
+                    Some(self.get_render_step(bvk, graph, &plan, pass, &physical)?)
+                }
+                PassKind::Compute => None,
+            };
+            steps.push(Step {
+                pass: PassHandle(p),
+                barriers,
+                render,
+            });
+        }
+
+        //  Leave everything where it is expected after the frame
+        let mut final_barriers = Barriers::default();
+        for (idx, image) in graph.images.iter().enumerate() {
+            if let (Some(final_layout), Some((physical_image, _))) =
+                (image.final_layout, physical[idx])
+            {
+                let state = self
+                    .image_states
+                    .entry(physical_image)
+                    .or_insert_with(|| SyncState::new(vk::ImageLayout::UNDEFINED));
+                let access = Access::read(
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::AccessFlags::empty(),
+                    final_layout,
+                );
+                if let Some(barrier) = state.access(access) {
+                    final_barriers.image(
+                        physical_image,
+                        format_aspect(image.desc().format),
+                        barrier,
+                    );
+                }
+            }
+        }
+
+        Some(CompiledGraph {
+            steps,
+            final_barriers,
+            views: physical
+                .iter()
+                .map(|physical| physical.map(|(_, view)| view))
+                .collect(),
+        })
+    }
+
+    //  What pipelines for `layout` are built against. With dynamic rendering, that's just `layout`.
+    pub fn pass_info(
+        &mut self,
+        bvk: &BabyVulkan,
+        layout: &PassLayout,
+        extent: vk::Extent2D,
+    ) -> Option<PassInfo> {
+        let render_pass = if bvk.dynamic_rendering.is_some() {
+            vk::RenderPass::null()
+        } else {
+            self.get_render_pass(bvk, RenderPassKey::compatible(layout))?
+        };
+        Some(PassInfo {
+            render_pass,
+            layout: layout.clone(),
+            extent,
+            subpass: 0,
+        })
+    }
+
+    fn get_transient(
+        &mut self,
+        bvk: &BabyVulkan,
+        desc: &ImageDesc,
+        slot: usize,
+    ) -> Option<(vk::Image, vk::ImageView)> {
+        let pool = self.images.entry(*desc).or_default();
+        while pool.len() <= slot {
+            let image = Image::create_multisampled(
+                bvk,
+                desc.format,
+                desc.usage,
+                vk::Extent3D {
+                    width: desc.extent.width,
+                    height: desc.extent.height,
+                    depth: 1,
+                },
+                desc.samples,
+            )?;
+            let view =
+                bvk.create_image_view(image.image, desc.format, format_aspect(desc.format))?;
+            pool.push((image, view));
+        }
+        let (image, view) = &pool[slot];
+        Some((image.image, *view))
+    }
+
+    fn get_render_pass(&mut self, bvk: &BabyVulkan, key: RenderPassKey) -> Option<vk::RenderPass> {
+        if let Some(&render_pass) = self.render_passes.get(&key) {
+            return Some(render_pass);
+        }
+        let render_pass = key.create_render_pass(bvk)?;
+        self.render_passes.insert(key, render_pass);
+        Some(render_pass)
+    }
+
+    fn get_render_step(
+        &mut self,
+        bvk: &BabyVulkan,
+        graph: &RenderGraph,
+        plan: &GraphPlan,
+        pass: &GraphPass,
+        physical: &[Option<(vk::Image, vk::ImageView)>],
+    ) -> Option<RenderStep> {
+        //  (Key, View, Clear value) of every attachment, in the order of `RenderPassKey`
+        let mut attachments = vec![];
+        let mut subpasses = vec![SubpassKey::default(); pass.subpasses as usize];
+        //  Where each image went in `attachments`, for the subpasses that read them
+        let mut indices: HashMap<usize, u32> = HashMap::new();
+        let mut extent = vk::Extent2D::default();
+        for (subpass_idx, subpass) in subpasses.iter_mut().enumerate() {
+            //  (Resource, Attachment, Key, View, Clear value)
+            let mut colors = vec![];
+            let mut depth = None;
+            let mut resolves = vec![];
+            for access in &pass.accesses {
+                let (idx, attachment) = match (access.resource, access.attachment) {
+                    (Resource::Image(idx), Some(attachment))
+                        if access.subpass == subpass_idx as u32 =>
+                    {
+                        (idx, attachment)
+                    }
+                    _ => continue,
+                };
+                if let Attachment::Input = attachment {
+                    subpass.inputs.push((*indices.get(&idx)?, access.layout));
+                    continue;
+                }
+                let desc = graph.images[idx].desc();
+                let (_, view) = physical[idx]?;
+                extent = desc.extent;
+
+                //  Only store what somebody is going to look at
+                let store_op = if plan.stores(access) {
+                    vk::AttachmentStoreOp::STORE
+                } else {
+                    vk::AttachmentStoreOp::DONT_CARE
+                };
+                let (load_op, clear_value) = match attachment {
+                    Attachment::Color(load) | Attachment::Depth(load) => load.op(),
+                    _ => (vk::AttachmentLoadOp::DONT_CARE, vk::ClearValue::default()),
+                };
+                let (stencil_load_op, stencil_store_op) = match (attachment, pass.stencil_ops) {
+                    (Attachment::Depth(_), Some(ops)) if has_stencil(desc.format) => {
+                        (ops.load, ops.store)
+                    }
+                    (Attachment::Depth(_), None) if has_stencil(desc.format) => (load_op, store_op),
+                    _ => (
+                        vk::AttachmentLoadOp::DONT_CARE,
+                        vk::AttachmentStoreOp::DONT_CARE,
+                    ),
+                };
+                let key = AttachmentKey {
+                    format: desc.format,
+                    samples: desc.samples,
+                    load_op,
+                    store_op,
+                    stencil_load_op,
+                    stencil_store_op,
+                    layout: access.layout,
+                };
+                let attachment_info = (idx, key, view, clear_value);
+                match attachment {
+                    Attachment::Color(_) => colors.push(attachment_info),
+                    Attachment::Depth(_) => depth = Some(attachment_info),
+                    _ => resolves.push(attachment_info),
+                }
+            }
+
+            let mut push = |(idx, key, view, clear_value)| {
+                indices.insert(idx, attachments.len() as u32);
+                attachments.push((key, view, clear_value));
+                attachments.len() as u32 - 1
+            };
+            subpass.colors = colors.into_iter().map(&mut push).collect();
+            subpass.depth = depth.map(&mut push);
+            subpass.resolves = resolves.into_iter().map(&mut push).collect();
+        }
+        let key = RenderPassKey {
+            attachments: attachments.iter().map(|(key, _, _)| *key).collect(),
+            subpasses,
+        };
+        let layouts = key.layouts();
+
+        //  Dynamic rendering has no subpasses, so those passes still get a render pass
+        if bvk.dynamic_rendering.is_some() && key.subpasses.len() == 1 {
+            let subpass = &key.subpasses[0];
+            let attachment = |&idx: &u32| attachments[idx as usize];
+            return Some(RenderStep {
+                extent,
+                layouts,
+                target: RenderTarget::dynamic(
+                    &subpass.colors.iter().map(attachment).collect::<Vec<_>>(),
+                    subpass.depth.as_ref().map(attachment).as_ref(),
+                    &subpass.resolves.iter().map(attachment).collect::<Vec<_>>(),
+                ),
+            });
+        }
+        let render_pass = self.get_render_pass(bvk, key)?;
+
+        let views: Vec<vk::ImageView> = attachments.iter().map(|(_, view, _)| *view).collect();
+        let framebuffer_key = (render_pass, views, extent);
+        let framebuffer = match self.framebuffers.get(&framebuffer_key) {
+            Some(&framebuffer) => framebuffer,
+            None => {
+                let framebuffer_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(render_pass)
+                    .attachments(&framebuffer_key.1)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1)
+                    .build();
+                let framebuffer =
+                    unsafe { bvk.dev.create_framebuffer(&framebuffer_info, None) }.ok()?;
+                self.framebuffers.insert(framebuffer_key, framebuffer);
+                framebuffer
+            }
+        };
+
+        Some(RenderStep {
+            extent,
+            layouts,
+            target: RenderTarget::Pass {
+                render_pass,
+                framebuffer,
+                clear_values: attachments
+                    .iter()
+                    .map(|(_, _, clear_value)| *clear_value)
+                    .collect(),
+            },
+        })
+    }
+
+    //  Everything that depends on the size of the swapchain.
+    //  The device has to be idle, since the images may still be in use otherwise.
+    pub fn clear(&mut self, bvk: &BabyVulkan) {
+        unsafe {
+            for (_, framebuffer) in self.framebuffers.drain() {
+                bvk.dev.destroy_framebuffer(framebuffer, None);
+            }
+            for (_, mut pool) in self.images.drain() {
+                for (image, view) in &mut pool {
+                    bvk.dev.destroy_image_view(*view, None);
+                    image.destroy(bvk);
+                }
+            }
+        }
+        //  Handles can be reused for new images, so forget everything
+        self.image_states.clear();
+        self.buffer_states.clear();
+    }
+
+    pub fn destroy(&mut self, bvk: &BabyVulkan) {
+        self.clear(bvk);
+        unsafe {
+            for (_, render_pass) in self.render_passes.drain() {
+                bvk.dev.destroy_render_pass(render_pass, None);
+            }
+        }
+    }
+}
+
+struct RenderStep {
+    extent: vk::Extent2D,
+    //  One for every subpass
+    layouts: Vec<PassLayout>,
+    target: RenderTarget,
+}
+
+enum RenderTarget {
+    Pass {
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        clear_values: Vec<vk::ClearValue>,
+    },
+    //  Attachments are given when recording, layouts are still up to the graph's barriers.
+    Dynamic {
+        colors: Vec<vk::RenderingAttachmentInfo>,
+        depth: Option<vk::RenderingAttachmentInfo>,
+        stencil: Option<vk::RenderingAttachmentInfo>,
+    },
+}
+
+impl RenderTarget {
+    fn dynamic(
+        colors: &[(AttachmentKey, vk::ImageView, vk::ClearValue)],
+        depth: Option<&(AttachmentKey, vk::ImageView, vk::ClearValue)>,
+        resolves: &[(AttachmentKey, vk::ImageView, vk::ClearValue)],
+    ) -> Self {
+        let attachment =
+            |&(key, view, clear_value): &(AttachmentKey, vk::ImageView, vk::ClearValue),
+             load_op,
+             store_op| {
+                vk::RenderingAttachmentInfo::builder()
+                    .image_view(view)
+                    .image_layout(key.layout)
+                    .load_op(load_op)
+                    .store_op(store_op)
+                    .clear_value(clear_value)
+                    .build()
+            };
+        RenderTarget::Dynamic {
+            colors: colors
+                .iter()
+                .enumerate()
+                .map(|(idx, color)| {
+                    let mut info = attachment(color, color.0.load_op, color.0.store_op);
+                    //  Resolves go with the color attachment instead of being attachments of their own
+                    if let Some(&(key, view, _)) = resolves.get(idx) {
+                        info.resolve_mode = vk::ResolveModeFlags::AVERAGE;
+                        info.resolve_image_view = view;
+                        info.resolve_image_layout = key.layout;
+                    }
+                    info
+                })
+                .collect(),
+            depth: depth.map(|depth| attachment(depth, depth.0.load_op, depth.0.store_op)),
+            //  The same view again, with the stencil's own ops
+            stencil: depth
+                .filter(|(key, _, _)| has_stencil(key.format))
+                .map(|depth| attachment(depth, depth.0.stencil_load_op, depth.0.stencil_store_op)),
+        }
+    }
+}
+
+impl RenderStep {
+    fn begin(&self, bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer) {
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+        match &self.target {
+            RenderTarget::Pass {
+                render_pass,
+                framebuffer,
+                clear_values,
+            } => {
+                let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+                    .render_pass(*render_pass)
+                    .render_area(render_area)
+                    .clear_values(clear_values)
+                    .framebuffer(*framebuffer)
+                    .build();
+                unsafe {
+                    bvk.dev.cmd_begin_render_pass(
+                        cmd_buf,
+                        &render_pass_begin_info,
+                        vk::SubpassContents::INLINE,
+                    )
+                };
+            }
+            RenderTarget::Dynamic {
+                colors,
+                depth,
+                stencil,
+            } => {
+                let mut rendering_info = vk::RenderingInfo::builder()
+                    .render_area(render_area)
+                    .layer_count(1)
+                    .color_attachments(colors);
+                if let Some(depth) = depth {
+                    rendering_info = rendering_info.depth_attachment(depth);
+                }
+                if let Some(stencil) = stencil {
+                    rendering_info = rendering_info.stencil_attachment(stencil);
+                }
+                if let Some(dynamic_rendering) = &bvk.dynamic_rendering {
+                    dynamic_rendering.cmd_begin_rendering(bvk, cmd_buf, &rendering_info);
+                }
+            }
+        }
+    }
+
+    fn end(&self, bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer) {
+        match &self.target {
+            RenderTarget::Pass { .. } => unsafe { bvk.dev.cmd_end_render_pass(cmd_buf) },
+            RenderTarget::Dynamic { .. } => {
+                if let Some(dynamic_rendering) = &bvk.dynamic_rendering {
+                    dynamic_rendering.cmd_end_rendering(bvk, cmd_buf);
+                }
+            }
+        }
+    }
+}
+
+//  Moves the pass being recorded on to its next subpass
+pub fn cmd_next_subpass(bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer) {
+    unsafe {
+        bvk.dev
+            .cmd_next_subpass(cmd_buf, vk::SubpassContents::INLINE)
+    };
+}
+
+struct Step {
+    pass: PassHandle,
+    barriers: Barriers,
+    render: Option<RenderStep>,
+}
+
+pub struct CompiledGraph {
+    steps: Vec<Step>,
+    final_barriers: Barriers,
+    views: Vec<Option<vk::ImageView>>,
+}
+
+impl CompiledGraph {
+    //  Whether `pass` survived culling
+    pub fn runs(&self, pass: PassHandle) -> bool {
+        self.steps.iter().any(|step| step.pass == pass)
+    }
+
+    //  The view of whatever image `image` ended up in, e.g. for descriptor sets
+    pub fn view(&self, image: ImageHandle) -> Option<vk::ImageView> {
+        self.views[image.idx]
+    }
+
+    //  What pipelines for `subpass` of `pass` have to be built against.
+    //  Unlike `GraphCache::pass_info`, this works for passes with more than one subpass.
+    pub fn pass_info(&self, pass: PassHandle, subpass: u32) -> Option<PassInfo> {
+        let render = self
+            .steps
+            .iter()
+            .find(|step| step.pass == pass)?
+            .render
+            .as_ref()?;
+        let render_pass = match &render.target {
+            RenderTarget::Pass { render_pass, .. } => *render_pass,
+            RenderTarget::Dynamic { .. } => vk::RenderPass::null(),
+        };
+        Some(PassInfo {
+            render_pass,
+            layout: render.layouts.get(subpass as usize)?.clone(),
+            extent: render.extent,
+            subpass,
+        })
+    }
+
+    //  `record` is called for every pass that runs, inside its render pass if it has one.
+    //  Passes with subpasses start in the first one and move on with `cmd_next_subpass`.
+    pub fn execute(
+        &self,
+        bvk: &BabyVulkan,
+        cmd_buf: vk::CommandBuffer,
+        mut record: impl FnMut(PassHandle, vk::CommandBuffer),
+    ) {
+        for step in &self.steps {
+            step.barriers.record(bvk, cmd_buf);
+            match &step.render {
+                Some(render) => {
+                    render.begin(bvk, cmd_buf);
+                    record(step.pass, cmd_buf);
+                    render.end(bvk, cmd_buf);
+                }
+                None => record(step.pass, cmd_buf),
+            }
+        }
+        self.final_barriers.record(bvk, cmd_buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENT: vk::Extent2D = vk::Extent2D {
+        width: 64,
+        height: 64,
+    };
+
+    fn clear() -> LoadOp {
+        LoadOp::Clear(vk::ClearValue::default())
+    }
+
+    fn hdr() -> ImageDesc {
+        ImageDesc::color(HDR_FORMAT, EXTENT, vk::SampleCountFlags::TYPE_1)
+    }
+
+    fn swapchain(graph: &mut RenderGraph) -> ImageHandle {
+        graph.import_image(
+            "swapchain",
+            ImportedImage {
+                image: vk::Image::null(),
+                view: vk::ImageView::null(),
+                desc: ImageDesc::color(
+                    vk::Format::B8G8R8A8_SRGB,
+                    EXTENT,
+                    vk::SampleCountFlags::TYPE_1,
+                ),
+                initial: None,
+            },
+        )
+    }
+
+    fn order(graph: &RenderGraph, plan: &GraphPlan) -> Vec<&'static str> {
+        plan.order.iter().map(|&p| graph.passes[p].name).collect()
+    }
+
+    #[test]
+    fn orders_by_dependencies_and_culls() {
+        let mut graph = RenderGraph::default();
+        let out = swapchain(&mut graph);
+        let scene = graph.create_image("scene", hdr());
+        let unused = graph.create_image("unused", hdr());
+        let pattern = graph.create_image("pattern", hdr());
+
+        //  Added before what it reads is written
+        let mut pass = graph.add_pass("post", PassKind::Graphics);
+        pass.sample(
+            ImageHandle {
+                idx: scene.idx,
+                version: 1,
+            },
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        let out = pass.color(out, LoadOp::DontCare);
+        graph.present(out);
+
+        let mut pass = graph.add_pass("scene", PassKind::Graphics);
+        pass.sample(
+            ImageHandle {
+                idx: pattern.idx,
+                version: 1,
+            },
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        pass.color(scene, clear());
+
+        let mut pass = graph.add_pass("nobody looks", PassKind::Graphics);
+        pass.color(unused, clear());
+
+        let mut pass = graph.add_pass("pattern", PassKind::Graphics);
+        pass.color(pattern, clear());
+
+        let plan = graph.plan().unwrap();
+        assert_eq!(order(&graph, &plan), ["pattern", "scene", "post"]);
+        assert_eq!(plan.slots[unused.idx], None);
+    }
+
+    #[test]
+    fn reads_finish_before_writes() {
+        let mut graph = RenderGraph::default();
+        let out = swapchain(&mut graph);
+        let image = graph.create_image("image", hdr());
+        let other = graph.create_image("other", hdr());
+
+        let mut pass = graph.add_pass("first", PassKind::Graphics);
+        let first = pass.color(image, clear());
+
+        let mut pass = graph.add_pass("second", PassKind::Graphics);
+        let second = pass.color(first, LoadOp::Load);
+
+        //  Reads the first version after the second one was made
+        let mut pass = graph.add_pass("late reader", PassKind::Graphics);
+        pass.sample(first, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        let other = pass.color(other, clear());
+
+        let mut pass = graph.add_pass("present", PassKind::Graphics);
+        pass.sample(second, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        pass.sample(other, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        let out = pass.color(out, LoadOp::DontCare);
+        graph.present(out);
+
+        let plan = graph.plan().unwrap();
+        assert_eq!(
+            order(&graph, &plan),
+            ["first", "late reader", "second", "present"]
+        );
+    }
+
+    #[test]
+    fn transients_share_images() {
+        let mut graph = RenderGraph::default();
+        let out = swapchain(&mut graph);
+        let scene = graph.create_image("scene", hdr());
+        let mut input = {
+            let mut pass = graph.add_pass("scene", PassKind::Graphics);
+            pass.color(scene, clear())
+        };
+        for _ in 0..4 {
+            let output = graph.create_image("bounce", hdr());
+            let mut pass = graph.add_pass("effect", PassKind::Graphics);
+            pass.sample(input, vk::PipelineStageFlags::FRAGMENT_SHADER);
+            input = pass.color(output, clear());
+        }
+        let mut pass = graph.add_pass("present", PassKind::Graphics);
+        pass.sample(input, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        let out = pass.color(out, LoadOp::DontCare);
+        graph.present(out);
+
+        //  Ping-pong between two images
+        let plan = graph.plan().unwrap();
+        let slots: Vec<Option<usize>> = plan.slots.clone();
+        assert_eq!(slots, [None, Some(0), Some(1), Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn stores_only_what_is_read() {
+        let mut graph = RenderGraph::default();
+        let out = swapchain(&mut graph);
+        let msaa = graph.create_image(
+            "msaa",
+            ImageDesc::color(HDR_FORMAT, EXTENT, vk::SampleCountFlags::TYPE_4),
+        );
+        let scene = graph.create_image("scene", hdr());
+
+        let mut pass = graph.add_pass("scene", PassKind::Graphics);
+        pass.color(msaa, clear());
+        let scene = pass.resolve(scene);
+
+        let mut pass = graph.add_pass("present", PassKind::Graphics);
+        pass.sample(scene, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        let out = pass.color(out, LoadOp::DontCare);
+        graph.present(out);
+
+        let plan = graph.plan().unwrap();
+        let stores: Vec<bool> = graph.passes[0]
+            .accesses
+            .iter()
+            .map(|access| plan.stores(access))
+            .collect();
+        assert_eq!(stores, [false, true]);
+    }
+
+    #[test]
+    fn subpasses_read_inputs() {
+        let mut graph = RenderGraph::default();
+        let out = swapchain(&mut graph);
+        let albedo = graph.create_image(
+            "albedo",
+            ImageDesc::subpass_input(vk::Format::R8G8B8A8_SRGB, EXTENT),
+        );
+        let depth = graph.create_image(
+            "depth",
+            ImageDesc::subpass_input(vk::Format::D32_SFLOAT, EXTENT),
+        );
+        let scene = graph.create_image("scene", hdr());
+
+        let mut pass = graph.add_pass("deferred", PassKind::Graphics);
+        let albedo = pass.color(albedo, clear());
+        let depth = pass.depth(depth, clear());
+        pass.next_subpass();
+        pass.input(albedo);
+        pass.input(depth);
+        let scene = pass.color(scene, clear());
+
+        let mut pass = graph.add_pass("present", PassKind::Graphics);
+        pass.sample(scene, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        let out = pass.color(out, LoadOp::DontCare);
+        graph.present(out);
+
+        //  The G-buffer never leaves the pass
+        let plan = graph.plan().unwrap();
+        let stores: Vec<bool> = graph.passes[0]
+            .accesses
+            .iter()
+            .map(|access| plan.stores(access))
+            .collect();
+        assert_eq!(stores, [false, false, false, false, true]);
+
+        let mut graph = RenderGraph::default();
+        let albedo = graph.create_image(
+            "albedo",
+            ImageDesc::subpass_input(vk::Format::R8G8B8A8_SRGB, EXTENT),
+        );
+        let scene = graph.create_image("scene", hdr());
+        let mut pass = graph.add_pass("early", PassKind::Graphics);
+        let albedo = pass.color(albedo, clear());
+        let mut pass = graph.add_pass("deferred", PassKind::Graphics);
+        pass.input(albedo);
+        pass.color(scene, clear());
+        assert!(matches!(
+            graph.plan().err(),
+            Some(GraphError::BadAttachments {
+                pass: "deferred",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_mistakes() {
+        let mut graph = RenderGraph::default();
+        let image = graph.create_image("image", hdr());
+        let other = graph.create_image("other", hdr());
+        let mut pass = graph.add_pass("reader", PassKind::Graphics);
+        pass.sample(image, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        pass.color(other, clear());
+        assert_eq!(
+            graph.plan().err(),
+            Some(GraphError::Uninitialized {
+                pass: "reader",
+                resource: "image"
+            })
+        );
+
+        let mut graph = RenderGraph::default();
+        let image = graph.create_image("image", hdr());
+        graph
+            .add_pass("first", PassKind::Graphics)
+            .color(image, clear());
+        graph
+            .add_pass("second", PassKind::Graphics)
+            .color(image, clear());
+        assert_eq!(
+            graph.plan().err(),
+            Some(GraphError::StaleWrite {
+                pass: "second",
+                resource: "image"
+            })
+        );
+
+        let mut graph = RenderGraph::default();
+        let image = graph.create_image("image", hdr());
+        let small = graph.create_image(
+            "small",
+            ImageDesc::depth(
+                vk::Format::D32_SFLOAT,
+                vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+                vk::SampleCountFlags::TYPE_1,
+            ),
+        );
+        let mut pass = graph.add_pass("pass", PassKind::Graphics);
+        pass.color(image, clear());
+        pass.depth(small, clear());
+        assert!(matches!(
+            graph.plan().err(),
+            Some(GraphError::BadAttachments { pass: "pass", .. })
+        ));
+    }
+}
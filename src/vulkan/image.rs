@@ -12,6 +12,17 @@ impl Image {
         format: vk::Format,
         usage: vk::ImageUsageFlags,
         extent: vk::Extent3D,
+    ) -> Option<Self> {
+        Self::create_multisampled(bvk, format, usage, extent, vk::SampleCountFlags::TYPE_1)
+    }
+
+    //  Only ever useful for attachments, nothing else can be multisampled.
+    pub fn create_multisampled(
+        bvk: &BabyVulkan,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        extent: vk::Extent3D,
+        samples: vk::SampleCountFlags,
     ) -> Option<Self> {
         let image_info = vk::ImageCreateInfo::builder()
             .format(format)
@@ -20,7 +31,7 @@ impl Image {
             .image_type(vk::ImageType::TYPE_2D)
             .mip_levels(1)
             .array_layers(1)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(samples)
             .tiling(vk::ImageTiling::OPTIMAL)
             .build();
 
@@ -0,0 +1,138 @@
+use super::*;
+
+//  Vertices and the triangles between them, ready to be uploaded.
+//  Triangles wind counter-clockwise when looked at from the side their normals point to.
+#[derive(Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    //  A unit cube around the origin.
+    //  Every face gets its own 4 vertices so that it can have its own normal and uvs.
+    pub fn cube() -> Self {
+        let mut cube = Geometry::default();
+        //  (Normal, Tangent)
+        let faces = [
+            ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+            ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0]),
+        ];
+        for (normal, tangent) in faces {
+            let normal = glm::Vec3::from(normal);
+            cube.quad(normal * 0.5, normal, tangent.into(), 0.5);
+        }
+        cube
+    }
+
+    //  A flat square of 2 by 2 around the origin, facing up
+    pub fn plane() -> Self {
+        let mut plane = Geometry::default();
+        plane.quad(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            1.0,
+        );
+        plane
+    }
+
+    //  Adds `other` after what's already here
+    pub fn append(&mut self, other: Geometry) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|idx| base + idx));
+    }
+
+    //  A square facing `normal`, with u going along `tangent`.
+    //  The texture is upright when `cross(normal, tangent)` is up.
+    fn quad(&mut self, center: glm::Vec3, normal: glm::Vec3, tangent: glm::Vec3, half_size: f32) {
+        let bitangent = normal.cross(&tangent);
+        let base = self.vertices.len() as u32;
+        for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let position = center
+                + tangent * (u * 2.0 - 1.0) * half_size
+                + bitangent * (v * 2.0 - 1.0) * half_size;
+            self.vertices.push(Vertex {
+                position: position.into(),
+                color: [1.0, 1.0, 1.0],
+                //  Images start at the top, so v goes against the bitangent
+                uv: [u, 1.0 - v],
+                normal: normal.into(),
+                tangent: [tangent.x, tangent.y, tangent.z, -1.0],
+            });
+        }
+        self.indices
+            .extend([0, 1, 2, 0, 2, 3].map(|idx| base + idx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(geometry: &Geometry) -> Vec<[Vertex; 3]> {
+        geometry
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| geometry.vertices[triangle[corner] as usize]))
+            .collect()
+    }
+
+    #[test]
+    fn cube_faces_point_outwards() {
+        let cube = Geometry::cube();
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.indices.len(), 36);
+        for [a, b, c] in triangles(&cube) {
+            let normal = glm::Vec3::from(a.normal);
+            //  Same normal for the whole face, facing away from the center
+            assert_eq!(a.normal, b.normal);
+            assert_eq!(a.normal, c.normal);
+            assert!(normal.dot(&glm::Vec3::from(a.position)) > 0.0);
+            //  Counter-clockwise seen from outside
+            let (a, b, c) = (
+                glm::Vec3::from(a.position),
+                glm::Vec3::from(b.position),
+                glm::Vec3::from(c.position),
+            );
+            assert!((b - a).cross(&(c - a)).dot(&normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn tangents_follow_the_uvs() {
+        let mut geometry = Geometry::cube();
+        geometry.append(Geometry::plane());
+        for vertex in &geometry.vertices {
+            let normal = glm::Vec3::from(vertex.normal);
+            let tangent = glm::vec3(vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]);
+            assert_eq!(normal.dot(&tangent), 0.0);
+            assert_eq!(normal.norm(), 1.0);
+            assert_eq!(tangent.norm(), 1.0);
+        }
+        //  Going along the tangent (and bitangent) of a face increases u (and v)
+        for [a, b, _] in triangles(&geometry) {
+            let edge = glm::Vec3::from(b.position) - glm::Vec3::from(a.position);
+            let tangent = glm::vec3(a.tangent[0], a.tangent[1], a.tangent[2]);
+            let bitangent = glm::Vec3::from(a.normal).cross(&tangent) * a.tangent[3];
+            let du = b.uv[0] - a.uv[0];
+            let dv = b.uv[1] - a.uv[1];
+            assert_eq!(edge.dot(&tangent) > 0.0, du > 0.0);
+            assert_eq!(edge.dot(&bitangent) > 0.0, dv > 0.0);
+        }
+    }
+
+    #[test]
+    fn append_moves_indices() {
+        let mut geometry = Geometry::cube();
+        geometry.append(Geometry::plane());
+        assert_eq!(geometry.vertices.len(), 28);
+        assert_eq!(&geometry.indices[36..], &[24, 25, 26, 24, 26, 27]);
+    }
+}
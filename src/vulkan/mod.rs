@@ -1,27 +1,62 @@
 use ash::*;
 use nalgebra_glm as glm;
 use std::ffi::{c_void, CString};
-use winit::{platform::unix::WindowExtUnix, window::Window};
+use winit::{event::VirtualKeyCode, platform::unix::WindowExtUnix, window::Window};
 use vk_mem::Alloc;
 
 mod baby;
 mod buf;
+mod compute;
+mod deferred;
+mod deletion;
 mod frame;
+mod graph;
 mod image;
+mod mesh;
+#[cfg(feature = "hot-reload")]
+mod permutations;
 mod pipeline;
 mod playground;
+mod post;
+mod reflect;
+#[cfg(feature = "hot-reload")]
+mod reload;
 mod render;
+mod settings;
+mod shaders;
+mod shadow;
+mod specialization;
 mod swapchain;
+mod sync;
 mod texture;
+mod timeline;
+mod transient;
 mod uniform;
 
 pub use baby::*;
 pub use buf::*;
+pub use compute::*;
+pub use deferred::*;
+pub use deletion::*;
 pub use frame::*;
+pub use graph::*;
 pub use image::*;
+pub use mesh::*;
+#[cfg(feature = "hot-reload")]
+pub use permutations::*;
 pub use pipeline::*;
 pub use playground::*;
+pub use post::*;
+pub use reflect::*;
+#[cfg(feature = "hot-reload")]
+pub use reload::*;
 pub use render::*;
+pub use settings::*;
+pub use shadow::*;
+pub use specialization::*;
 pub use swapchain::*;
+pub use sync::*;
 pub use texture::*;
+pub use timeline::*;
+pub use transient::*;
 pub use uniform::*;
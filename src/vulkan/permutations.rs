@@ -0,0 +1,11 @@
+//  Extra variants of a shader, compiled with some macros defined.
+//  This is used by both build.rs and `ShaderReloader`, so it can't depend on anything else.
+//  ("fragment", "fragment_untextured", &[("UNTEXTURED", None)]) =>
+//  { src/fragment.frag + #define UNTEXTURED -> shaders::FRAGMENT_UNTEXTURED }
+pub type Defines = &'static [(&'static str, Option<&'static str>)];
+pub type Permutation = (&'static str, &'static str, Defines);
+
+pub const PERMUTATIONS: &[Permutation] = &[
+    //  Ripple the vertices instead of scaling them
+    ("animate", "animate_wave", &[("WAVE", None)]),
+];
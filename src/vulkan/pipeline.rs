@@ -1,25 +1,98 @@
 use super::*;
+use std::collections::HashMap;
+
+//  Which specialization constants and fixed function state a pipeline was built with.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct PipelineVariant {
+    pub vert: Specialization,
+    pub frag: Specialization,
+    pub stencil: StencilMode,
+}
+
+//  Everything that marks or tests the stencil uses this value.
+pub const STENCIL_REFERENCE: u32 = 1;
+
+//  How a pipeline treats the stencil buffer.
+//  Without a stencil in the render pass, the test always passes.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StencilMode {
+    #[default]
+    Disabled,
+    //  Draw as usual and mark everything drawn.
+    Write,
+    //  Only draw on top of marked pixels, for masking.
+    Inside,
+    //  Only draw around marked pixels without touching depth, for outlines.
+    Outside,
+}
+
+impl StencilMode {
+    fn op_state(self) -> vk::StencilOpState {
+        let (compare_op, pass_op) = match self {
+            StencilMode::Disabled => (vk::CompareOp::ALWAYS, vk::StencilOp::KEEP),
+            StencilMode::Write => (vk::CompareOp::ALWAYS, vk::StencilOp::REPLACE),
+            StencilMode::Inside => (vk::CompareOp::EQUAL, vk::StencilOp::KEEP),
+            StencilMode::Outside => (vk::CompareOp::NOT_EQUAL, vk::StencilOp::KEEP),
+        };
+        vk::StencilOpState::builder()
+            .compare_op(compare_op)
+            .pass_op(pass_op)
+            .fail_op(vk::StencilOp::KEEP)
+            .depth_fail_op(vk::StencilOp::KEEP)
+            .compare_mask(0xff)
+            .write_mask(0xff)
+            .reference(STENCIL_REFERENCE)
+            .build()
+    }
+}
 
 pub struct VulkanPipeline {
     pub pipeline: vk::Pipeline,
     pub pipeline_layout: vk::PipelineLayout,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
     pub vert_shader: vk::ShaderModule,
     pub frag_shader: vk::ShaderModule,
 }
 
 impl VulkanPipeline {
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
         bvk: &BabyVulkan,
-        render: &VulkanRender,
-        extent: vk::Extent2D,
+        pass: &PassInfo,
+        vert_code: &[u32],
+        frag_code: &[u32],
+        variant: &PipelineVariant,
         bindings: &[vk::VertexInputBindingDescription],
         attributes: &[vk::VertexInputAttributeDescription],
-        push_constants: &[vk::PushConstantRange],
         descriptor_set_layouts: &[vk::DescriptorSetLayout],
     ) -> Option<Self> {
+        //  Find out what the shaders want from us
+        let mut interface = ShaderInterface::reflect_specialized(&[
+            (vert_code, &variant.vert),
+            (frag_code, &variant.frag),
+        ])
+        .and_then(|interface| {
+            interface.check_vertex_attributes(attributes)?;
+            Ok(interface)
+        })
+        .map_err(|e| println!("[Pipeline] {}", e))
+        .ok()?;
+
         //  Create the shaders
-        let vert_shader = Self::create_shader_module(bvk, "./vertex.spv")?;
-        let frag_shader = Self::create_shader_module(bvk, "./fragment.spv")?;
+        let vert_shader = Self::create_shader_module(bvk, vert_code)?;
+        let frag_shader = Self::create_shader_module(bvk, frag_code)?;
+
+        //  Create Specialization Info
+        let (vert_map_entries, vert_data) = variant.vert.map_entries_and_data();
+        let vert_specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&vert_map_entries)
+            .data(&vert_data)
+            .build();
+        let (frag_map_entries, frag_data) = variant.frag.map_entries_and_data();
+        let frag_specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&frag_map_entries)
+            .data(&frag_data)
+            .build();
 
         //  Create Shader Stage Info
         let entry_point = CString::new("main").ok()?;
@@ -27,11 +100,13 @@ impl VulkanPipeline {
             .name(&entry_point)
             .stage(vk::ShaderStageFlags::VERTEX)
             .module(vert_shader)
+            .specialization_info(&vert_specialization_info)
             .build();
         let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
             .name(&entry_point)
             .stage(vk::ShaderStageFlags::FRAGMENT)
             .module(frag_shader)
+            .specialization_info(&frag_specialization_info)
             .build();
 
         //  Create Vertex Input State Info
@@ -64,7 +139,7 @@ impl VulkanPipeline {
         //  Create Multisample Info
         let multisample_info = vk::PipelineMultisampleStateCreateInfo::builder()
             .sample_shading_enable(false)
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(pass.layout.samples)
             .min_sample_shading(1.0)
             .sample_mask(&[])
             .alpha_to_coverage_enable(false)
@@ -77,13 +152,16 @@ impl VulkanPipeline {
             .blend_enable(false)
             .build();
 
+        //  One per color attachment, depth only passes have none
+        let color_blend_states = vec![color_blend_state; pass.layout.colors.len()];
         let color_blend_info = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
-            .attachments(&[color_blend_state])
+            .attachments(&color_blend_states)
             .build();
 
         //  Create Viewport State
+        let extent = pass.extent;
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
             .viewports(&[vk::Viewport {
                 x: 0.0,
@@ -100,38 +178,57 @@ impl VulkanPipeline {
             .build();
 
         //  Create Depth Stencil
+        let stencil_op_state = variant.stencil.op_state();
         let depth_info = vk::PipelineDepthStencilStateCreateInfo::builder()
             .depth_test_enable(true)
-            .depth_write_enable(true)
+            .depth_write_enable(variant.stencil != StencilMode::Outside)
             .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
             .depth_bounds_test_enable(false)
-            .stencil_test_enable(false)
+            .stencil_test_enable(variant.stencil != StencilMode::Disabled)
+            .front(stencil_op_state)
+            .back(stencil_op_state)
             .min_depth_bounds(0.0)
             .max_depth_bounds(1.0)
             .build();
 
         //  Create Pipeline Layout
         let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
-            .push_constant_ranges(push_constants)
+            .push_constant_ranges(&interface.push_constants)
             .set_layouts(descriptor_set_layouts)
             .build();
         let pipeline_layout =
             unsafe { bvk.dev.create_pipeline_layout(&pipeline_layout_info, None) }.ok()?;
 
+        //  Create Rendering Info, for when there is no render pass to take the formats from
+        let depth_format = pass.layout.depth.unwrap_or(vk::Format::UNDEFINED);
+        let mut rendering_info = vk::PipelineRenderingCreateInfo::builder()
+            .color_attachment_formats(&pass.layout.colors)
+            .depth_attachment_format(depth_format)
+            .stencil_attachment_format(if has_stencil(depth_format) {
+                depth_format
+            } else {
+                vk::Format::UNDEFINED
+            })
+            .build();
+
         //  Create the Graphics Pipeline
-        let graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(&[vert_shader_stage_info, frag_shader_stage_info])
+        let stages = [vert_shader_stage_info, frag_shader_stage_info];
+        let mut graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
             .vertex_input_state(&vertex_input_state_info)
             .input_assembly_state(&input_assembly_info)
             .viewport_state(&viewport_state)
             .rasterization_state(&raster_info)
             .multisample_state(&multisample_info)
             .color_blend_state(&color_blend_info)
-            .render_pass(render.render_pass)
-            .subpass(0)
+            .render_pass(pass.render_pass)
+            .subpass(pass.subpass)
             .layout(pipeline_layout)
-            .depth_stencil_state(&depth_info)
-            .build();
+            .depth_stencil_state(&depth_info);
+        if pass.render_pass == vk::RenderPass::null() {
+            graphics_pipeline_info = graphics_pipeline_info.push_next(&mut rendering_info);
+        }
+        let graphics_pipeline_info = graphics_pipeline_info.build();
         let pipeline = unsafe {
             bvk.dev.create_graphics_pipelines(
                 vk::PipelineCache::null(),
@@ -146,18 +243,20 @@ impl VulkanPipeline {
         Some(VulkanPipeline {
             pipeline,
             pipeline_layout,
+            push_constant_ranges: std::mem::take(&mut interface.push_constants),
             vert_shader,
             frag_shader,
         })
     }
 
-    fn create_shader_module(bvk: &BabyVulkan, path: &str) -> Option<vk::ShaderModule> {
-        let code = std::fs::read(path).unwrap_or_else(|_| panic!("Failed to read {}", path));
-        let shader_info = vk::ShaderModuleCreateInfo::builder()
-            .code(unsafe {
-                std::slice::from_raw_parts(code.as_ptr() as *const u32, code.len() / (32 / 8))
-            })
-            .build();
+    pub fn read_shader_code(code: &[u8]) -> Option<Vec<u32>> {
+        load_spirv(code)
+            .map_err(|e| println!("[Pipeline] {}", e))
+            .ok()
+    }
+
+    fn create_shader_module(bvk: &BabyVulkan, code: &[u32]) -> Option<vk::ShaderModule> {
+        let shader_info = vk::ShaderModuleCreateInfo::builder().code(code).build();
         unsafe { bvk.dev.create_shader_module(&shader_info, None) }.ok()
     }
 
@@ -170,3 +269,56 @@ impl VulkanPipeline {
         }
     }
 }
+
+//  `data` starts at offset 0 and is split up between the ranges of `layout`.
+//  Each piece is pushed with exactly the stages of the range it lands in.
+pub fn cmd_push_constants(
+    bvk: &BabyVulkan,
+    cmd_buf: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    ranges: &[vk::PushConstantRange],
+    data: &[u8],
+) {
+    for range in ranges {
+        let start = range.offset as usize;
+        let end = ((range.offset + range.size) as usize).min(data.len());
+        if start < end {
+            unsafe {
+                bvk.dev.cmd_push_constants(
+                    cmd_buf,
+                    layout,
+                    range.stage_flags,
+                    range.offset,
+                    &data[start..end],
+                )
+            };
+        }
+    }
+}
+
+//  Pipelines are built lazily the first time a variant is asked for, then reused.
+#[derive(Default)]
+pub struct PipelineVariants {
+    pipelines: HashMap<PipelineVariant, VulkanPipeline>,
+}
+
+impl PipelineVariants {
+    pub fn get_or_create(
+        &mut self,
+        variant: &PipelineVariant,
+        create: impl FnOnce() -> Option<VulkanPipeline>,
+    ) -> Option<&VulkanPipeline> {
+        if !self.pipelines.contains_key(variant) {
+            self.pipelines.insert(variant.clone(), create()?);
+        }
+        self.pipelines.get(variant)
+    }
+
+    //  Anything the pipelines depend on (render pass, extent, shaders) changed, so throw them all
+    //  out.
+    pub fn clear(&mut self, bvk: &BabyVulkan) {
+        self.pipelines
+            .drain()
+            .for_each(|(_, pipeline)| pipeline.destroy(bvk));
+    }
+}
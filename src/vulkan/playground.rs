@@ -1,96 +1,282 @@
 use super::*;
 
-const FRAME_BUFFER_COUNT: usize = 2;
+//  `layout(constant_id = N)` in vertex.vert
+const TINTED_CONSTANT_ID: u32 = 0;
+
+//  `layout(constant_id = N)` in fragment.frag
+const TEXTURED_CONSTANT_ID: u32 = 0;
+const SHOW_UV_CONSTANT_ID: u32 = 1;
+const PATTERN_CONSTANT_ID: u32 = 2;
+const OUTLINE_CONSTANT_ID: u32 = 3;
+const OFFSCREEN_CONSTANT_ID: u32 = 4;
+const LIT_CONSTANT_ID: u32 = 5;
+
+const OFFSCREEN_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 512,
+    height: 512,
+};
+const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+//  How much bigger the outline is than the cube it goes around
+const OUTLINE_SCALE: f32 = 1.06;
+//  The masked cube is bigger so that it isn't hidden behind the one that masks it.
+const MASKED_SCALE: f32 = 1.3;
+
+//  Stencil tricks, cycled with O.
+#[derive(Clone, Copy, Debug)]
+enum StencilDemo {
+    Off,
+    //  A flat border around the cube
+    Outline,
+    //  A second cube spinning the other way, only visible through the first one
+    Mask,
+}
+
+const PATTERN_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 256,
+    height: 256,
+};
+
+//  Part of `ibo`
+#[derive(Clone, Copy)]
+struct Mesh {
+    first_index: u32,
+    index_count: u32,
+}
+
+const CUBE: Mesh = Mesh {
+    first_index: 0,
+    index_count: 36,
+};
+//  Right after the cube, so that the shadow falls somewhere
+const GROUND: Mesh = Mesh {
+    first_index: 36,
+    index_count: 6,
+};
+const GROUND_HEIGHT: f32 = -1.2;
+const GROUND_SIZE: f32 = 3.0;
+
+//  Where the camera is, a bit above the ground
+const EYE: [f32; 3] = [0.0, 1.0, 2.2];
+
+//  Blinn-Phong lighting, see `UniformData`
+const LIGHT_COLOR: [f32; 3] = [1.0, 0.95, 0.85];
+const AMBIENT_COLOR: [f32; 3] = [0.2, 0.22, 0.28];
+const SPECULAR_STRENGTH: f32 = 0.5;
+const SHININESS: f32 = 32.0;
+
+//  Deferred shading adds lots of small lights, circling just above the ground
+const POINT_LIGHT_COUNT: usize = 32;
+const POINT_LIGHT_RADIUS: f32 = 1.2;
+
+//  One mesh, ready to be recorded by `draw`
+struct Draw {
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    mesh: Mesh,
+    push_constants: PushConstantData,
+}
+
+impl Draw {
+    fn new(pipeline: &VulkanPipeline, mesh: Mesh, view_proj: &glm::Mat4, model: glm::Mat4) -> Self {
+        Draw {
+            pipeline: pipeline.pipeline,
+            pipeline_layout: pipeline.pipeline_layout,
+            push_constant_ranges: pipeline.push_constant_ranges.clone(),
+            mesh,
+            push_constants: PushConstantData {
+                mvp: view_proj * model,
+                model,
+            },
+        }
+    }
+}
 
 pub struct VulkanPlayground {
+    settings: Settings,
     bvk: BabyVulkan,
     swappy: VulkanSwapchain,
     render: VulkanRender,
-    uniform: Uniform<FRAME_BUFFER_COUNT>,
-    pipeline: VulkanPipeline,
+    //  Images, render passes and framebuffers for the frame graph built by `render`
+    graph_cache: GraphCache,
+    shadow: ShadowMap,
+    //  Used instead of `render` with G
+    deferred: Deferred,
+    post: PostChain,
+    uniform: Uniform,
+    pipelines: PipelineVariants,
+    //  A cube is rendered into `offscreen` every frame and shown on the main cube with R.
+    //  That pass gets its own descriptor sets that never point back at `offscreen`.
+    offscreen: Texture,
+    offscreen_uniform: Uniform,
+    offscreen_pipelines: PipelineVariants,
+    show_offscreen: bool,
+    textured: bool,
+    show_uv: bool,
+    show_pattern: bool,
+    stencil_demo: StencilDemo,
+    vert_code: Vec<u32>,
+    frag_code: Vec<u32>,
+    //  `None` when the sources aren't around, e.g. when running from somewhere else.
+    #[cfg(feature = "hot-reload")]
+    reloader: Option<ShaderReloader>,
+    #[cfg(feature = "hot-reload")]
+    uniform_bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    #[cfg(feature = "hot-reload")]
+    animate_bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    #[cfg(feature = "hot-reload")]
+    pattern_bindings: Vec<vk::DescriptorSetLayoutBinding>,
 
     vbo: Buffer,
     ibo: Buffer,
+    //  `vbo` is animated by `animate` every frame based on `rest_vbo`.
+    //  Only the cube is, the ground after it stays where it is.
+    rest_vbo: Buffer,
+    vertex_count: u32,
+    animate: ComputePipeline,
+    //  The `WAVE` permutation of `animate`, toggled with W.
+    animate_wave: ComputePipeline,
+    wave: bool,
+    animate_descriptors: ComputeDescriptors,
     texture: Texture,
+    //  Redrawn by `pattern_pipeline` every frame, shown instead of `texture` with P.
+    pattern: Texture,
+    pattern_pipeline: ComputePipeline,
+    pattern_descriptors: ComputeDescriptors,
 
     cmd_pool: vk::CommandPool,
+    etc_cmd_buf: vk::CommandBuffer,
     etc_fence: vk::Fence,
 
-    frames: Frames<FRAME_BUFFER_COUNT>,
+    frames: Frames,
+    //  Uniforms, reset whenever a frame comes around. Has room for `MAX_FRAMES_IN_FLIGHT`, so
+    //  that it stays put when the number of frames changes.
+    transient: TransientBuffer,
 
     start: std::time::Instant,
+    //  Nothing is drawn while the window has no area, or while the last resize didn't go through
+    minimized: bool,
 }
 
 impl VulkanPlayground {
-    pub fn create(window: &Window, w: u32, h: u32) -> Option<Self> {
-        let mut bvk = BabyVulkan::create(window)?;
-        let swappy = VulkanSwapchain::create(&bvk, w, h)?;
-        let render = VulkanRender::create(&bvk, &swappy)?;
+    pub fn create(window: &Window, w: u32, h: u32, settings: Settings) -> Option<Self> {
+        let mut bvk = BabyVulkan::create(window, settings.dynamic_rendering, settings.timeline)?;
+        let swappy = VulkanSwapchain::create(&bvk, w, h, &settings, None)?;
+        let samples = bvk.get_sample_count(settings.samples);
+        if samples.as_raw() != settings.samples {
+            println!(
+                "[Playground] {} samples are not supported, using {}",
+                settings.samples,
+                samples.as_raw()
+            );
+        }
+        let render = VulkanRender::create(&bvk, swappy.extent, samples, StencilOps::default())?;
+        if !render.has_stencil() {
+            println!("[Playground] No depth format with a stencil, the stencil demos won't work");
+        }
 
         let cmd_pool = bvk.create_command_pool()?;
         let etc_cmd_buf = bvk.create_primary_command_buffer(cmd_pool)?;
         let etc_fence = bvk.create_fence(false)?;
 
-        let texture = Texture::create("texture.jpg", &mut bvk, etc_fence, etc_cmd_buf)?;
-        let uniform = Uniform::<FRAME_BUFFER_COUNT>::create(&bvk, &texture)?;
-        let pipeline = VulkanPipeline::create(
+        //  Embedded like the shaders so that we can run from any directory.
+        let texture = Texture::create(
+            include_bytes!("../../texture.jpg"),
+            &mut bvk,
+            etc_fence,
+            etc_cmd_buf,
+        )?;
+        let pattern = Texture::create_storage(&bvk, PATTERN_EXTENT)?;
+
+        //  Create the Post Chain
+        let mut effects = PostEffect::builtin()?;
+        match &settings.post {
+            Some(order) => arrange_effects(&mut effects, order),
+            None => default_effects(&mut effects, swappy.format, swappy.color_space),
+        }
+        set_output_encoding(&mut effects, swappy.color_space);
+        let mut graph_cache = GraphCache::default();
+        let post = PostChain::create(
+            &mut bvk,
+            &mut graph_cache,
+            &swappy,
+            effects,
+            etc_fence,
+            etc_cmd_buf,
+        )?;
+        let offscreen = Texture::create_attachment(&bvk, OFFSCREEN_FORMAT, OFFSCREEN_EXTENT)?;
+        let shadow = ShadowMap::create(&bvk, &mut graph_cache, glm::vec3(1.0, 2.0, 0.5))?;
+
+        //  Load the Shaders
+        #[cfg(not(feature = "hot-reload"))]
+        let (vert_code, frag_code) = (
+            VulkanPipeline::read_shader_code(shaders::VERTEX)?,
+            VulkanPipeline::read_shader_code(shaders::FRAGMENT)?,
+        );
+        //  With `hot-reload`, they are compiled straight from `src/` instead.
+        //  If that fails, start with whatever build.rs embedded.
+        #[cfg(feature = "hot-reload")]
+        let reloader = ShaderReloader::create(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+        #[cfg(feature = "hot-reload")]
+        if reloader.is_none() {
+            println!("[Playground] Hot reload is disabled, using the embedded shaders");
+        }
+        #[cfg(feature = "hot-reload")]
+        let (vert_code, frag_code) = (
+            reloader
+                .as_ref()
+                .and_then(|reloader| reloader.compile("vertex"))
+                .or_else(|| VulkanPipeline::read_shader_code(shaders::VERTEX))?,
+            reloader
+                .as_ref()
+                .and_then(|reloader| reloader.compile("fragment"))
+                .or_else(|| VulkanPipeline::read_shader_code(shaders::FRAGMENT))?,
+        );
+
+        //  Derive the Descriptor Set Layout from the Shaders
+        let uniform_bindings = ShaderInterface::reflect(&[&vert_code, &frag_code])
+            .and_then(|interface| Ok(interface.descriptor_set()?.to_vec()))
+            .map_err(|e| println!("[Reflect] {}", e))
+            .ok()?;
+        let frame_count = settings.frames_in_flight;
+        let transient = TransientBuffer::create(&bvk, TRANSIENT_FRAME_SIZE, MAX_FRAMES_IN_FLIGHT)?;
+        let uniform = Uniform::create(
             &bvk,
-            &render,
-            swappy.extent,
-            &[Vertex::bindings()],
-            &Vertex::attributes(),
-            &[PushConstantData::push_constants()],
-            &[uniform.descriptor_set_layout],
+            &uniform_bindings,
+            &[
+                (1, &texture),
+                (2, &pattern),
+                (3, &offscreen),
+                (4, &shadow.texture),
+            ],
+            transient.buffer.buf,
+        )?;
+        let offscreen_uniform = Uniform::create(
+            &bvk,
+            &uniform_bindings,
+            &[
+                (1, &texture),
+                (2, &pattern),
+                (3, &texture),
+                (4, &shadow.texture),
+            ],
+            transient.buffer.buf,
+        )?;
+        let deferred = Deferred::create(
+            &bvk,
+            &texture,
+            &shadow.texture,
+            transient.buffer.buf,
+            frame_count,
         )?;
 
         //  Define Vertex and Index Data
-        let vertices = vec![
-            Vertex {
-                position: [-0.5, -0.5, -0.5],
-                color: [1.0, 1.0, 1.0],
-                uv: [0.0, 1.0],
-            },
-            Vertex {
-                position: [-0.5, 0.5, -0.5],
-                color: [0.85, 0.85, 0.85],
-                uv: [0.0, 0.0],
-            },
-            Vertex {
-                position: [0.5, 0.5, -0.5],
-                color: [0.7, 0.7, 0.7],
-                uv: [1.0, 0.0],
-            },
-            Vertex {
-                position: [0.5, -0.5, -0.5],
-                color: [0.55, 0.55, 0.55],
-                uv: [1.0, 1.0],
-            },
-            Vertex {
-                position: [-0.5, -0.5, 0.5],
-                color: [0.4, 0.4, 0.4],
-                uv: [0.0, 1.0],
-            },
-            Vertex {
-                position: [-0.5, 0.5, 0.5],
-                color: [0.25, 0.25, 0.25],
-                uv: [0.0, 0.0],
-            },
-            Vertex {
-                position: [0.5, 0.5, 0.5],
-                color: [0.1, 0.1, 0.1],
-                uv: [1.0, 0.0],
-            },
-            Vertex {
-                position: [0.5, -0.5, 0.5],
-                color: [0.0, 0.0, 0.0],
-                uv: [1.0, 1.0],
-            },
-        ];
-        //  Because I'm too lazy to define vertices for every side, the texture will look off. :)
-        let indices = vec![
-            0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7, 4, 0, 1, 4, 1, 5, 6, 2, 3, 6, 3, 7, 4, 0, 3, 4, 3,
-            7, 1, 5, 6, 1, 6, 2,
-        ];
+        //  The ground goes right after the cube, see `CUBE` and `GROUND`.
+        let mut geometry = Geometry::cube();
+        let cube_vertex_count = geometry.vertices.len() as u32;
+        geometry.append(Geometry::plane());
+        let (vertices, indices) = (geometry.vertices, geometry.indices);
 
         //  Transfer a vbo Staging Buffer to GPU Memory
         let mut staging_vbo =
@@ -98,9 +284,17 @@ impl VulkanPlayground {
         let vbo = Buffer::create(
             staging_vbo.size,
             &bvk,
-            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
         )?;
         Buffer::upload_copy_data(&staging_vbo, &vbo, &bvk, etc_fence, etc_cmd_buf)?;
+        let rest_vbo = Buffer::create(
+            staging_vbo.size,
+            &bvk,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+        Buffer::upload_copy_data(&staging_vbo, &rest_vbo, &bvk, etc_fence, etc_cmd_buf)?;
         staging_vbo.destroy(&mut bvk);
 
         //  Transfer an ibo Staging Buffer to GPU Memory
@@ -114,44 +308,216 @@ impl VulkanPlayground {
         Buffer::upload_copy_data(&staging_ibo, &ibo, &bvk, etc_fence, etc_cmd_buf)?;
         staging_ibo.destroy(&mut bvk);
 
-        Some(VulkanPlayground {
+        //  Create the Compute Pipeline that Animates `vbo`
+        let animate_code = VulkanPipeline::read_shader_code(shaders::ANIMATE)?;
+        let animate_bindings = ShaderInterface::reflect(&[&animate_code])
+            .and_then(|interface| Ok(interface.descriptor_set()?.to_vec()))
+            .map_err(|e| println!("[Reflect] {}", e))
+            .ok()?;
+        let animate_descriptors = ComputeDescriptors::create(
+            &bvk,
+            &animate_bindings,
+            &[
+                ComputeResource::StorageBuffer(&rest_vbo),
+                ComputeResource::StorageBuffer(&vbo),
+            ],
+        )?;
+        let animate = ComputePipeline::create(
+            &bvk,
+            &animate_code,
+            &[animate_descriptors.descriptor_set_layout],
+        )?;
+        let animate_wave = ComputePipeline::create(
+            &bvk,
+            &VulkanPipeline::read_shader_code(shaders::ANIMATE_WAVE)?,
+            &[animate_descriptors.descriptor_set_layout],
+        )?;
+
+        //  Create the Compute Pipeline that Draws `pattern`
+        let pattern_code = VulkanPipeline::read_shader_code(shaders::PATTERN)?;
+        let pattern_bindings = ShaderInterface::reflect(&[&pattern_code])
+            .and_then(|interface| Ok(interface.descriptor_set()?.to_vec()))
+            .map_err(|e| println!("[Reflect] {}", e))
+            .ok()?;
+        let pattern_descriptors = ComputeDescriptors::create(
+            &bvk,
+            &pattern_bindings,
+            &[ComputeResource::StorageImage(pattern.image_view)],
+        )?;
+        let pattern_pipeline = ComputePipeline::create(
+            &bvk,
+            &pattern_code,
+            &[pattern_descriptors.descriptor_set_layout],
+        )?;
+
+        let mut playground = VulkanPlayground {
             vbo,
             ibo,
+            rest_vbo,
+            vertex_count: cube_vertex_count,
+            animate,
+            animate_wave,
+            wave: false,
+            animate_descriptors,
             texture,
+            pattern,
+            pattern_pipeline,
+            pattern_descriptors,
 
-            frames: Frames::create(&bvk, cmd_pool)?,
+            frames: Frames::create(&bvk, cmd_pool, frame_count, swappy.swapchain_images.len())?,
+            transient,
             cmd_pool,
+            etc_cmd_buf,
             etc_fence,
 
+            settings,
             bvk,
             swappy,
             render,
+            graph_cache,
+            shadow,
+            deferred,
+            post,
             uniform,
-            pipeline,
+            pipelines: PipelineVariants::default(),
+            offscreen,
+            offscreen_uniform,
+            offscreen_pipelines: PipelineVariants::default(),
+            show_offscreen: false,
+            textured: true,
+            show_uv: false,
+            show_pattern: false,
+            stencil_demo: StencilDemo::Off,
+            vert_code,
+            frag_code,
+            #[cfg(feature = "hot-reload")]
+            reloader,
+            #[cfg(feature = "hot-reload")]
+            uniform_bindings,
+            #[cfg(feature = "hot-reload")]
+            animate_bindings,
+            #[cfg(feature = "hot-reload")]
+            pattern_bindings,
 
             start: std::time::Instant::now(),
-        })
+            minimized: false,
+        };
+
+        //  Build the starting variant up front so that broken shaders are caught right away.
+        let variant = playground.get_variant();
+        playground.get_pipeline(&variant, false)?;
+        playground.get_pipeline(&PipelineVariant::default(), true)?;
+        Some(playground)
     }
 
     pub fn render(&mut self, window: &Window) -> Option<()> {
         let dims = window.inner_size();
         let w = dims.width;
         let h = dims.height;
+        if self.minimized {
+            self.resize(w, h)?;
+            if self.minimized {
+                return Some(());
+            }
+        }
         let current_frame = self.frames.get_current_frame();
         let current_cmd_buf = self.frames.cmd_bufs[current_frame];
-        let current_render_semaphore = self.frames.render_semaphores[current_frame];
         let current_present_semaphore = self.frames.present_semaphores[current_frame];
-        let current_frame_fence = self.frames.frame_fences[current_frame];
         let elapsed = self.start.elapsed().as_millis();
+
+        #[cfg(feature = "hot-reload")]
+        self.reload_shaders();
+
+        //  Everything on screen is seen from the same place
+        let view_mat = glm::look_at(
+            &EYE.into(),
+            &glm::vec3(0.0, -0.3, 0.0),
+            &glm::vec3(0.0, 1.0, 0.0),
+        );
+        let mut perspective =
+            glm::perspective(800.0 / 600.0, 90.0 * (glm::pi::<f32>() / 180.0), 0.1, 100.0);
+        //  Vulkan's y points down, so flip it to keep the ground below the cube
+        perspective[(1, 1)] *= -1.0;
+        let view_proj = perspective * view_mat;
+        let spin = |direction: f32, scale: f32| {
+            let model_mat = glm::identity();
+            let model_mat = glm::rotate(
+                &model_mat,
+                direction * elapsed as f32 / 8.0 * (glm::pi::<f32>() / 180.0),
+                &glm::vec3(1.0, 0.0, 1.0),
+            );
+            glm::scale(&model_mat, &glm::vec3(scale, scale, scale))
+        };
+
+        //  Only the cube casts a shadow, the ground just receives it
+        let light_view_proj = self.shadow.light_view_proj();
+        let shadow_draw = Draw::new(
+            &self.shadow.pipeline,
+            CUBE,
+            &light_view_proj,
+            spin(1.0, 1.0),
+        );
+        let ground_model = glm::scale(
+            &glm::translate(&glm::identity(), &glm::vec3(0.0, GROUND_HEIGHT, 0.0)),
+            &glm::vec3(GROUND_SIZE, 1.0, GROUND_SIZE),
+        );
+
+        //  The ground goes first, then the cube.
+        //  For the stencil demos, the cube marks the stencil and a second cube is drawn against it.
+        //  Deferred shading gets its draws later, once the graph made its pass.
+        let mut draws = vec![
+            (self.get_ground_variant(), GROUND, ground_model),
+            (self.get_variant(), CUBE, spin(1.0, 1.0)),
+        ];
+        match self.stencil_demo {
+            StencilDemo::Off => {}
+            StencilDemo::Outline => {
+                draws[1].0.stencil = StencilMode::Write;
+                draws.push((self.get_outline_variant(), CUBE, spin(1.0, OUTLINE_SCALE)));
+            }
+            StencilDemo::Mask => {
+                draws[1].0.stencil = StencilMode::Write;
+                let mut masked = self.get_variant();
+                masked.frag = masked.frag.constant(SHOW_UV_CONSTANT_ID, true);
+                masked.stencil = StencilMode::Inside;
+                draws.push((masked, CUBE, spin(-1.0, MASKED_SCALE)));
+            }
+        }
+        if self.settings.deferred {
+            draws.clear();
+        }
+        let draws = draws
+            .into_iter()
+            .map(|(variant, mesh, model)| self.get_draw(&variant, false, mesh, &view_proj, model))
+            .collect::<Option<Vec<_>>>()?;
+
+        //  The cube that ends up on the faces of the other one, plainly textured and upright
+        let offscreen_perspective = glm::perspective(
+            OFFSCREEN_EXTENT.width as f32 / OFFSCREEN_EXTENT.height as f32,
+            90.0 * (glm::pi::<f32>() / 180.0),
+            0.1,
+            100.0,
+        );
+        let offscreen_view_mat = glm::translate(&glm::identity(), &glm::vec3(0.0, 0.0, -2.0));
+        let offscreen_model_mat = glm::rotate(
+            &glm::identity(),
+            elapsed as f32 / 4.0 * (glm::pi::<f32>() / 180.0),
+            &glm::vec3(0.0, 1.0, 0.0),
+        );
+        let offscreen_draw = self.get_draw(
+            &PipelineVariant::default(),
+            true,
+            CUBE,
+            &(offscreen_perspective * offscreen_view_mat),
+            offscreen_model_mat,
+        )?;
+
         unsafe {
             //  Wait for the GPU to finish munching on our previous work and resize if neccesary
-            assert!(self
-                .bvk
-                .dev
-                .wait_for_fences(&[current_frame_fence], true, u64::MAX)
-                .is_ok());
-            assert!(self.bvk.dev.reset_fences(&[current_frame_fence]).is_ok());
-            let (swapchain_image_idx, _suboptimal) =
+            self.frames.wait_for_frame(&mut self.bvk)?;
+            self.transient.reset(current_frame);
+            //  A suboptimal swapchain still works, so this frame is drawn before replacing it.
+            let (swapchain_image_idx, suboptimal) =
                 match self.swappy.swapchain_ext.acquire_next_image(
                     self.swappy.swapchain,
                     u64::MAX,
@@ -159,144 +525,242 @@ impl VulkanPlayground {
                     vk::Fence::null(),
                 ) {
                     Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                        self.resize(w, h);
-                        return Some(());
+                        return self.resize(w, h);
                     }
                     Err(_) => None?,
                     Ok(ret) => ret,
                 };
+            //  Everything that can fail goes before `begin_submit`, whose wait has to be signaled.
+            let recorded = (|| -> Option<()> {
+                assert!(self
+                    .bvk
+                    .dev
+                    .reset_command_buffer(current_cmd_buf, vk::CommandBufferResetFlags::empty())
+                    .is_ok());
 
-            assert!(self
-                .bvk
-                .dev
-                .reset_command_buffer(current_cmd_buf, vk::CommandBufferResetFlags::empty())
-                .is_ok());
-
-            let cmd_begin_info = vk::CommandBufferBeginInfo::builder()
-                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
-                .build();
+                let cmd_begin_info = vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+                    .build();
 
-            //  Record the command buffer
-            self.bvk
-                .dev
-                .begin_command_buffer(current_cmd_buf, &cmd_begin_info)
-                .ok()?;
-            {
-                let color_clear_value = vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.2, 0.3, 0.5, 1.0],
-                    },
-                };
-                let depth_clear_value = vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
+                //  Record the command buffer
+                self.bvk
+                    .dev
+                    .begin_command_buffer(current_cmd_buf, &cmd_begin_info)
+                    .ok()?;
+                let light_dir = self.shadow.light_dir;
+                let uniform_data = UniformData {
+                    color: glm::vec4(1.0, 0.0, 0.0, 1.0) * ((elapsed as f32 / 500.0).sin() + 1.2),
+                    light_view_proj,
+                    light_dir: glm::vec4(light_dir.x, light_dir.y, light_dir.z, 0.0),
+                    light_color: glm::vec4(LIGHT_COLOR[0], LIGHT_COLOR[1], LIGHT_COLOR[2], 0.0),
+                    ambient_color: glm::vec4(
+                        AMBIENT_COLOR[0],
+                        AMBIENT_COLOR[1],
+                        AMBIENT_COLOR[2],
+                        0.0,
+                    ),
+                    camera_position: glm::vec4(EYE[0], EYE[1], EYE[2], 1.0),
+                    specular: glm::vec4(SPECULAR_STRENGTH, SHININESS, 0.0, 0.0),
                 };
+                //  Every pass reads the same one
+                let scene_uniform = self.transient.push_uniform(current_frame, &uniform_data)?;
+                let scene_offsets = [scene_uniform];
 
-                let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
-                    .render_pass(self.render.render_pass)
-                    .render_area(vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: self.swappy.extent,
-                    })
-                    .clear_values(&[color_clear_value, depth_clear_value])
-                    .framebuffer(*self.render.framebuffers.get(swapchain_image_idx as usize)?)
-                    .build();
-                self.bvk.dev.cmd_begin_render_pass(
-                    current_cmd_buf,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
+                //  Describe the Frame
+                //  The graph works out the order, the barriers and the images in between.
+                let mut graph = RenderGraph::default();
+                let swapchain = graph.import_image(
+                    "swapchain",
+                    self.swappy.import(swapchain_image_idx as usize),
                 );
-                {
-                    self.bvk.dev.cmd_bind_pipeline(
-                        current_cmd_buf,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.pipeline.pipeline,
-                    );
-                    let mut push_constant = PushConstantData {
-                        mvp: glm::identity(),
-                    };
-
-                    let view_mat = glm::identity();
-                    let view_mat = glm::translate(&view_mat, &glm::vec3(0.0, 0.0, -2.0));
-
-                    let model_mat = glm::identity();
-                    let model_mat = glm::rotate(
-                        &model_mat,
-                        elapsed as f32 / 8.0 * (glm::pi::<f32>() / 180.0),
-                        &glm::vec3(1.0, 0.0, 1.0),
-                    );
+                let pattern = graph.import_image(
+                    "pattern",
+                    ImportedImage::texture(&self.pattern, PATTERN_EXTENT),
+                );
+                let offscreen = graph.import_image(
+                    "offscreen",
+                    ImportedImage::texture(&self.offscreen, OFFSCREEN_EXTENT),
+                );
+                let vbo = graph.import_buffer("vbo", self.vbo.buf);
+                let rest_vbo = graph.import_buffer("rest_vbo", self.rest_vbo.buf);
 
-                    let perspective = glm::perspective(
-                        800.0 / 600.0,
-                        90.0 * (glm::pi::<f32>() / 180.0),
-                        0.1,
-                        100.0,
-                    );
+                let mut pass = graph.add_pass("pattern", PassKind::Compute);
+                let pattern =
+                    pass.write_storage_image(pattern, vk::PipelineStageFlags::COMPUTE_SHADER);
+                let pattern_pass = pass.handle();
 
-                    push_constant.mvp = perspective * view_mat * model_mat;
-
-                    self.bvk.dev.cmd_push_constants(
-                        current_cmd_buf,
-                        self.pipeline.pipeline_layout,
-                        vk::ShaderStageFlags::VERTEX,
-                        0,
-                        std::slice::from_raw_parts(
-                            (&push_constant as *const PushConstantData) as *const u8,
-                            std::mem::size_of::<PushConstantData>(),
-                        ),
-                    );
-                    self.bvk
-                        .dev
-                        .cmd_bind_vertex_buffers(current_cmd_buf, 0, &[self.vbo.buf], &[0]);
-                    self.bvk.dev.cmd_bind_index_buffer(
-                        current_cmd_buf,
-                        self.ibo.buf,
-                        0,
-                        vk::IndexType::UINT32,
-                    );
+                let mut pass = graph.add_pass("animate", PassKind::Compute);
+                pass.read_storage_buffer(rest_vbo, vk::PipelineStageFlags::COMPUTE_SHADER);
+                let vbo = pass.write_storage_buffer(vbo, vk::PipelineStageFlags::COMPUTE_SHADER);
+                let animate_pass = pass.handle();
 
-                    let uniform_data = UniformData {
-                        color: glm::vec4(1.0, 0.0, 0.0, 1.0)
-                            * ((elapsed as f32 / 500.0).sin() + 1.2),
-                    };
-                    self.uniform.uniform_bufs[current_frame].map_copy_data(
-                        &self.bvk,
-                        &uniform_data as *const UniformData as *const u8,
-                        std::mem::size_of::<UniformData>(),
-                    );
+                let (mut pass, shadow_map) = self.shadow.add_pass(&mut graph);
+                pass.vertex_buffer(vbo);
+                let shadow_pass = pass.handle();
 
-                    self.bvk.dev.cmd_bind_descriptor_sets(
-                        current_cmd_buf,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.pipeline.pipeline_layout,
-                        0,
-                        &[self.uniform.descriptor_sets[current_frame]],
-                        &[],
-                    );
+                //  Both passes bind all the textures, so both have to say so
+                let offscreen_depth = graph.create_image(
+                    "offscreen_depth",
+                    ImageDesc::depth(
+                        self.render.depth_format,
+                        OFFSCREEN_EXTENT,
+                        vk::SampleCountFlags::TYPE_1,
+                    ),
+                );
+                let mut pass = graph.add_pass("offscreen", PassKind::Graphics);
+                pass.vertex_buffer(vbo);
+                pass.sample(pattern, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                pass.sample(shadow_map, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                let offscreen = pass.color(
+                    offscreen,
+                    LoadOp::Clear(vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: [0.9, 0.6, 0.3, 1.0],
+                        },
+                    }),
+                );
+                pass.depth(offscreen_depth, LoadOp::Clear(DEPTH_CLEAR_VALUE));
+                let offscreen_pass = pass.handle();
 
-                    //  self.bvk.dev.cmd_draw(self.cmd_buf, 3, 1, 0, 0);
-                    self.bvk
-                        .dev
-                        .cmd_draw_indexed(current_cmd_buf, 36, 1, 0, 0, 0);
-                }
-                self.bvk.dev.cmd_end_render_pass(current_cmd_buf);
+                //  Nothing samples `pattern` or `offscreen` with deferred shading, so those get culled.
+                let clear_color = [0.2, 0.3, 0.5, 1.0];
+                let (scene, scene_pass) = if self.settings.deferred {
+                    let (mut pass, scene) =
+                        self.deferred
+                            .add_pass(&mut graph, self.render.extent, clear_color);
+                    pass.vertex_buffer(vbo);
+                    pass.sample(shadow_map, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                    (scene, pass.handle())
+                } else {
+                    let (mut pass, scene) = self.render.add_pass(&mut graph, clear_color);
+                    pass.vertex_buffer(vbo);
+                    pass.sample(pattern, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                    pass.sample(offscreen, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                    pass.sample(shadow_map, vk::PipelineStageFlags::FRAGMENT_SHADER);
+                    (scene, pass.handle())
+                };
+
+                //  Post Process into the Swapchain
+                let swapchain = self.post.add_passes(&mut graph, scene, swapchain);
+                graph.present(swapchain);
+
+                //  Record the Frame
+                let compiled = self.graph_cache.compile(&self.bvk, &graph)?;
+                self.post.prepare(&self.bvk, &compiled)?;
+                let lights = LightData::new(glm::inverse(&view_proj), &point_lights(elapsed));
+                let lights = self.transient.push_uniform(current_frame, &lights)?;
+                self.deferred.prepare(&self.bvk, &compiled, current_frame)?;
+                let deferred_draws = if self.settings.deferred {
+                    self.get_deferred_draws(&view_proj, ground_model, spin(1.0, 1.0))?
+                } else {
+                    vec![]
+                };
+                compiled.execute(&self.bvk, current_cmd_buf, |pass, cmd_buf| {
+                    if pass == pattern_pass {
+                        let push_constant = PatternPushConstantData {
+                            time: elapsed as f32 / 1000.0,
+                        };
+                        self.pattern_pipeline.dispatch(
+                            &self.bvk,
+                            cmd_buf,
+                            &[self.pattern_descriptors.descriptor_set],
+                            std::slice::from_raw_parts(
+                                (&push_constant as *const PatternPushConstantData) as *const u8,
+                                std::mem::size_of::<PatternPushConstantData>(),
+                            ),
+                            [PATTERN_EXTENT.width, PATTERN_EXTENT.height, 1],
+                        );
+                    } else if pass == animate_pass {
+                        let push_constant = AnimatePushConstantData {
+                            time: elapsed as f32 / 1000.0,
+                            vertex_count: self.vertex_count,
+                        };
+                        let animate = if self.wave {
+                            &self.animate_wave
+                        } else {
+                            &self.animate
+                        };
+                        animate.dispatch(
+                            &self.bvk,
+                            cmd_buf,
+                            &[self.animate_descriptors.descriptor_set],
+                            std::slice::from_raw_parts(
+                                (&push_constant as *const AnimatePushConstantData) as *const u8,
+                                std::mem::size_of::<AnimatePushConstantData>(),
+                            ),
+                            [self.vertex_count, 1, 1],
+                        );
+                    } else if pass == shadow_pass {
+                        self.draw(cmd_buf, &shadow_draw, None);
+                    } else if pass == offscreen_pass {
+                        self.draw(
+                            cmd_buf,
+                            &offscreen_draw,
+                            Some((self.offscreen_uniform.descriptor_set, &scene_offsets)),
+                        );
+                    } else if pass == scene_pass && self.settings.deferred {
+                        let descriptor_set = self.deferred.uniform.descriptor_set;
+                        for draw in &deferred_draws {
+                            self.draw(cmd_buf, draw, Some((descriptor_set, &scene_offsets)));
+                        }
+                        self.deferred.record_lighting(
+                            &self.bvk,
+                            cmd_buf,
+                            current_frame,
+                            scene_uniform,
+                            lights,
+                        );
+                    } else if pass == scene_pass {
+                        for draw in &draws {
+                            self.draw(
+                                cmd_buf,
+                                draw,
+                                Some((self.uniform.descriptor_set, &scene_offsets)),
+                            );
+                        }
+                    } else {
+                        self.post.record(&self.bvk, cmd_buf, pass);
+                    }
+                });
+                self.bvk.dev.end_command_buffer(current_cmd_buf).ok()
+            })();
+
+            //  With more images than frames, an older frame might still be drawing into this one.
+            //  Only now that something will be submitted, or the next wait would never end.
+            let done = self
+                .frames
+                .begin_submit(&self.bvk, swapchain_image_idx as usize)?;
+            let current_render_semaphore =
+                self.frames.render_semaphores[swapchain_image_idx as usize];
+            if recorded.is_none() {
+                //  The acquire still has to be waited on and `done` signaled. An image that wasn't
+                //  drawn can't be presented, so only a new swapchain gives it back.
+                self.bvk.submit(
+                    self.bvk.graphics_queue,
+                    &[],
+                    &[(
+                        current_present_semaphore,
+                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    )],
+                    &[],
+                    done,
+                )?;
+                self.frames.advance();
+                self.resize(w, h)?;
+                None?;
             }
-            self.bvk.dev.end_command_buffer(current_cmd_buf).ok()?;
 
             //  Ready to render!
-            let submit_info = vk::SubmitInfo::builder()
-                .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-                .wait_semaphores(&[current_present_semaphore])
-                .signal_semaphores(&[current_render_semaphore])
-                .command_buffers(&[current_cmd_buf])
-                .build();
-
-            self.bvk
-                .dev
-                .queue_submit(self.bvk.graphics_queue, &[submit_info], current_frame_fence)
-                .unwrap();
+            self.bvk.submit(
+                self.bvk.graphics_queue,
+                &[current_cmd_buf],
+                &[(
+                    current_present_semaphore,
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                )],
+                &[current_render_semaphore],
+                done,
+            )?;
 
             //  Ready to display!
             let present_info = vk::PresentInfoKHR::builder()
@@ -306,37 +770,582 @@ impl VulkanPlayground {
                 .build();
 
             self.frames.advance();
-            match self
+            let recreate = match self
                 .swappy
                 .swapchain_ext
                 .queue_present(self.bvk.present_queue, &present_info)
             {
-                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.resize(w, h)?,
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
                 Err(_) => None?,
-                _ => {}
+                Ok(present_suboptimal) => suboptimal || present_suboptimal,
+            };
+            if recreate {
+                self.resize(w, h)?;
             }
         }
         Some(())
     }
 
+    //  Until this goes through, `render` doesn't draw and keeps calling it.
+    //  A minimized window isn't a failure, there is just nothing to do until it comes back.
     pub fn resize(&mut self, w: u32, h: u32) -> Option<()> {
-        unsafe { self.bvk.dev.device_wait_idle().unwrap() };
-        self.pipeline.destroy(&self.bvk);
-        self.render.destroy(&self.bvk);
-        self.swappy.destroy(&self.bvk);
-        self.swappy = VulkanSwapchain::create(&self.bvk, w, h)?;
-        self.render = VulkanRender::create(&self.bvk, &self.swappy)?;
-        self.pipeline = VulkanPipeline::create(
+        self.minimized = true;
+        let extent = VulkanSwapchain::surface_extent(&self.bvk, w, h)?;
+        if extent.width == 0 || extent.height == 0 {
+            return Some(());
+        }
+        unsafe { self.bvk.dev.device_wait_idle().ok()? };
+        //  If this fails, everything still fits the old swapchain
+        let swappy = VulkanSwapchain::create(&self.bvk, w, h, &self.settings, Some(&self.swappy))?;
+        std::mem::replace(&mut self.swappy, swappy).destroy(&self.bvk);
+        self.frames
+            .set_image_count(&self.bvk, self.swappy.swapchain_images.len())?;
+        //  Pipelines are rebuilt lazily by `render`
+        self.pipelines.clear(&self.bvk);
+        self.graph_cache.clear(&self.bvk);
+        let samples = self.bvk.get_sample_count(self.settings.samples);
+        self.render = VulkanRender::create(
             &self.bvk,
-            &self.render,
             self.swappy.extent,
+            samples,
+            StencilOps::default(),
+        )?;
+        set_output_encoding(&mut self.post.effects, self.swappy.color_space);
+        self.post
+            .resize(&self.bvk, &mut self.graph_cache, &self.swappy)?;
+        self.minimized = false;
+        Some(())
+    }
+
+    //  Read texture.jpg again and swap it in, without waiting for the frames in flight.
+    pub fn reload_texture(&mut self) -> Option<()> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/texture.jpg");
+        let file = std::fs::read(path)
+            .map_err(|e| println!("[Playground] Could not read {}: {}", path, e))
+            .ok()?;
+        let texture = Texture::create(&file, &mut self.bvk, self.etc_fence, self.etc_cmd_buf)?;
+        let mut pools = vec![];
+        for uniform in [
+            &mut self.uniform,
+            &mut self.offscreen_uniform,
+            &mut self.deferred.uniform,
+        ] {
+            pools.push(uniform.swap_texture(&self.bvk, &self.texture, &texture)?);
+        }
+        let mut old = std::mem::replace(&mut self.texture, texture);
+        self.frames.retire(move |bvk| {
+            for pool in pools {
+                unsafe { bvk.dev.destroy_descriptor_pool(pool, None) };
+            }
+            old.destroy(bvk);
+        });
+        Some(())
+    }
+
+    //  Everything kept per frame is made again for `count` frames.
+    pub fn set_frames_in_flight(&mut self, count: usize) -> Option<()> {
+        unsafe { self.bvk.dev.device_wait_idle().ok()? };
+        let frames = Frames::create(
+            &self.bvk,
+            self.cmd_pool,
+            count,
+            self.swappy.swapchain_images.len(),
+        )?;
+        std::mem::replace(&mut self.frames, frames).destroy(&mut self.bvk);
+        self.deferred.set_frame_count(&self.bvk, count)?;
+        self.settings.frames_in_flight = count;
+        Some(())
+    }
+
+    pub fn key_pressed(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::T => {
+                self.textured = !self.textured;
+                println!("[Playground] Textured: {}", self.textured);
+            }
+            VirtualKeyCode::U => {
+                self.show_uv = !self.show_uv;
+                println!("[Playground] Show UVs: {}", self.show_uv);
+            }
+            VirtualKeyCode::P => {
+                self.show_pattern = !self.show_pattern;
+                println!("[Playground] Show pattern: {}", self.show_pattern);
+            }
+            //  Turn post effects on and off, in the order they run
+            VirtualKeyCode::Key1
+            | VirtualKeyCode::Key2
+            | VirtualKeyCode::Key3
+            | VirtualKeyCode::Key4
+            | VirtualKeyCode::Key5
+            | VirtualKeyCode::Key6 => {
+                let idx = key as usize - VirtualKeyCode::Key1 as usize;
+                if let Some(effect) = self.post.effects.get_mut(idx) {
+                    effect.enabled = !effect.enabled;
+                    println!(
+                        "[Playground] Post effect {}: {}",
+                        effect.name, effect.enabled
+                    );
+                }
+            }
+            //  Both tonemap and hdr start with the exposure
+            VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                let factor = if key == VirtualKeyCode::LBracket {
+                    1.0 / 1.25
+                } else {
+                    1.25
+                };
+                for name in ["tonemap", "hdr"] {
+                    if let Some(effect) = self.post.effect_mut(name) {
+                        effect.params[0] *= factor;
+                    }
+                }
+                if let Some(tonemap) = self.post.effect_mut("tonemap") {
+                    println!("[Playground] Exposure: {:.2}", tonemap.params[0]);
+                }
+            }
+            VirtualKeyCode::L => match self.reload_texture() {
+                Some(()) => println!("[Playground] Reloaded texture.jpg"),
+                None => println!("[Playground] Kept the old texture"),
+            },
+            VirtualKeyCode::R => {
+                self.show_offscreen = !self.show_offscreen;
+                println!("[Playground] Show offscreen cube: {}", self.show_offscreen);
+            }
+            VirtualKeyCode::W => {
+                self.wave = !self.wave;
+                println!("[Playground] Wave: {}", self.wave);
+            }
+            VirtualKeyCode::G => {
+                self.settings.deferred = !self.settings.deferred;
+                println!("[Playground] Deferred shading: {}", self.settings.deferred);
+            }
+            VirtualKeyCode::O => {
+                self.stencil_demo = match self.stencil_demo {
+                    StencilDemo::Off => StencilDemo::Outline,
+                    StencilDemo::Outline => StencilDemo::Mask,
+                    StencilDemo::Mask => StencilDemo::Off,
+                };
+                println!("[Playground] Stencil demo: {:?}", self.stencil_demo);
+            }
+            VirtualKeyCode::F => {
+                let count = self.settings.frames_in_flight % MAX_FRAMES_IN_FLIGHT + 1;
+                if self.set_frames_in_flight(count).is_none() {
+                    println!("[Playground] Could not make {} frames in flight", count);
+                }
+                println!(
+                    "[Playground] Frames in flight: {}",
+                    self.settings.frames_in_flight
+                );
+            }
+            //  A new present mode needs a new swapchain
+            VirtualKeyCode::V => {
+                self.settings.present_mode = self.settings.present_mode.next();
+                let extent = self.swappy.extent;
+                if self.resize(extent.width, extent.height).is_none() {
+                    println!("[Playground] Could not rebuild the swapchain");
+                }
+                println!(
+                    "[Playground] Present mode: {:?} ({:?})",
+                    self.settings.present_mode, self.swappy.present_mode
+                );
+            }
+            //  Step through every sample count the device has, then wrap around to no MSAA.
+            VirtualKeyCode::M => {
+                let current = self.render.samples.as_raw();
+                let next = self.bvk.get_sample_count(current * 2).as_raw();
+                self.settings.samples = if next > current { next } else { 1 };
+                println!("[Playground] MSAA samples: {}", self.settings.samples);
+                //  Same as a resize, the render targets and pipelines have to be rebuilt.
+                let extent = self.swappy.extent;
+                if self.resize(extent.width, extent.height).is_none() {
+                    println!("[Playground] Could not rebuild the render targets");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //  Grab (or build) the pipeline for `variant`, either on screen or in `offscreen`
+    fn get_pipeline(
+        &mut self,
+        variant: &PipelineVariant,
+        offscreen: bool,
+    ) -> Option<&VulkanPipeline> {
+        let (pipelines, layout, extent) = if offscreen {
+            (
+                &mut self.offscreen_pipelines,
+                PassLayout {
+                    colors: vec![OFFSCREEN_FORMAT],
+                    depth: Some(self.render.depth_format),
+                    samples: vk::SampleCountFlags::TYPE_1,
+                    resolve: false,
+                },
+                OFFSCREEN_EXTENT,
+            )
+        } else {
+            (
+                &mut self.pipelines,
+                self.render.layout(),
+                self.render.extent,
+            )
+        };
+        let pass = self.graph_cache.pass_info(&self.bvk, &layout, extent)?;
+        pipelines.get_or_create(variant, || {
+            VulkanPipeline::create(
+                &self.bvk,
+                &pass,
+                &self.vert_code,
+                &self.frag_code,
+                variant,
+                &[Vertex::bindings()],
+                &Vertex::attributes(),
+                &[self.uniform.descriptor_set_layout],
+            )
+        })
+    }
+
+    fn get_draw(
+        &mut self,
+        variant: &PipelineVariant,
+        offscreen: bool,
+        mesh: Mesh,
+        view_proj: &glm::Mat4,
+        model: glm::Mat4,
+    ) -> Option<Draw> {
+        let pipeline = self.get_pipeline(variant, offscreen)?;
+        Some(Draw::new(pipeline, mesh, view_proj, model))
+    }
+
+    //  Record `draw` into the render pass that's currently going on in `cmd_buf`.
+    //  Pipelines without descriptor sets, like the shadow one, get `None`. The others get their
+    //  set and its dynamic offsets.
+    fn draw(
+        &self,
+        cmd_buf: vk::CommandBuffer,
+        draw: &Draw,
+        descriptor_set: Option<(vk::DescriptorSet, &[u32])>,
+    ) {
+        let push_constant = &draw.push_constants;
+        unsafe {
+            self.bvk
+                .dev
+                .cmd_bind_pipeline(cmd_buf, vk::PipelineBindPoint::GRAPHICS, draw.pipeline);
+            cmd_push_constants(
+                &self.bvk,
+                cmd_buf,
+                draw.pipeline_layout,
+                &draw.push_constant_ranges,
+                std::slice::from_raw_parts(
+                    (push_constant as *const PushConstantData) as *const u8,
+                    std::mem::size_of::<PushConstantData>(),
+                ),
+            );
+            self.bvk
+                .dev
+                .cmd_bind_vertex_buffers(cmd_buf, 0, &[self.vbo.buf], &[0]);
+            self.bvk
+                .dev
+                .cmd_bind_index_buffer(cmd_buf, self.ibo.buf, 0, vk::IndexType::UINT32);
+            if let Some((descriptor_set, dynamic_offsets)) = descriptor_set {
+                self.bvk.dev.cmd_bind_descriptor_sets(
+                    cmd_buf,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    draw.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    dynamic_offsets,
+                );
+            }
+            self.bvk.dev.cmd_draw_indexed(
+                cmd_buf,
+                draw.mesh.index_count,
+                1,
+                draw.mesh.first_index,
+                0,
+                0,
+            );
+        }
+    }
+
+    fn get_variant(&self) -> PipelineVariant {
+        PipelineVariant {
+            vert: Specialization::default(),
+            frag: Specialization::default()
+                .constant(TEXTURED_CONSTANT_ID, self.textured)
+                .constant(SHOW_UV_CONSTANT_ID, self.show_uv)
+                .constant(PATTERN_CONSTANT_ID, self.show_pattern)
+                .constant(OFFSCREEN_CONSTANT_ID, self.show_offscreen)
+                .constant(LIT_CONSTANT_ID, true),
+            stencil: StencilMode::Disabled,
+        }
+    }
+
+    //  Plain and lit, without the cube's pulsing color
+    fn get_ground_variant(&self) -> PipelineVariant {
+        PipelineVariant {
+            vert: Specialization::default().constant(TINTED_CONSTANT_ID, false),
+            frag: Specialization::default()
+                .constant(TEXTURED_CONSTANT_ID, false)
+                .constant(LIT_CONSTANT_ID, true),
+            stencil: StencilMode::Disabled,
+        }
+    }
+
+    //  Deferred shading only has the plain cube and the ground, the other toggles are forward only.
+    fn get_deferred_draws(
+        &mut self,
+        view_proj: &glm::Mat4,
+        ground_model: glm::Mat4,
+        cube_model: glm::Mat4,
+    ) -> Option<Vec<Draw>> {
+        let ground = PipelineVariant {
+            vert: Specialization::default().constant(TINTED_CONSTANT_ID, false),
+            frag: Specialization::default().constant(TEXTURED_CONSTANT_ID, false),
+            stencil: StencilMode::Disabled,
+        };
+        let cube = PipelineVariant {
+            vert: Specialization::default(),
+            frag: Specialization::default().constant(TEXTURED_CONSTANT_ID, self.textured),
+            stencil: StencilMode::Disabled,
+        };
+        Some(vec![
+            Draw::new(
+                self.deferred.pipeline(&self.bvk, &ground)?,
+                GROUND,
+                view_proj,
+                ground_model,
+            ),
+            Draw::new(
+                self.deferred.pipeline(&self.bvk, &cube)?,
+                CUBE,
+                view_proj,
+                cube_model,
+            ),
+        ])
+    }
+
+    fn get_outline_variant(&self) -> PipelineVariant {
+        PipelineVariant {
+            vert: Specialization::default(),
+            frag: Specialization::default().constant(OUTLINE_CONSTANT_ID, true),
+            stencil: StencilMode::Outside,
+        }
+    }
+
+    //  Rebuild pipelines whenever one of their shaders changes on disk.
+    //  If the new source fails to compile, we keep using the old pipeline.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self) {
+        let changed = match self.reloader.as_mut() {
+            Some(reloader) => reloader.poll(),
+            None => return,
+        };
+        if changed
+            .iter()
+            .any(|name| name == "vertex" || name == "fragment")
+        {
+            self.reload_graphics_pipeline();
+        }
+        let post_shaders: Vec<&String> = changed
+            .iter()
+            .filter(|name| self.post.has_shader(name))
+            .collect();
+        for name in post_shaders {
+            let code = match self
+                .reloader
+                .as_ref()
+                .and_then(|reloader| reloader.compile(name))
+            {
+                Some(code) => code,
+                None => continue,
+            };
+            if unsafe { self.bvk.dev.device_wait_idle() }.is_err() {
+                continue;
+            }
+            if self.post.reload(&self.bvk, name, code).is_some() {
+                println!("[Reload] Rebuilt post effect {}", name);
+            }
+        }
+        if changed.iter().any(|name| name == "animate") {
+            let layout = self.animate_descriptors.descriptor_set_layout;
+            if let Some(animate) =
+                self.reload_compute_pipeline("animate", &self.animate_bindings, layout)
+            {
+                let old = std::mem::replace(&mut self.animate, animate);
+                self.frames.retire(move |bvk| old.destroy(bvk));
+            }
+            if let Some(animate_wave) =
+                self.reload_compute_pipeline("animate_wave", &self.animate_bindings, layout)
+            {
+                let old = std::mem::replace(&mut self.animate_wave, animate_wave);
+                self.frames.retire(move |bvk| old.destroy(bvk));
+            }
+        }
+        if changed
+            .iter()
+            .any(|name| name == "shadow" || name == "depth_only")
+        {
+            self.reload_shadow_pipeline();
+        }
+        let deferred_shaders: Vec<&String> = changed
+            .iter()
+            .filter(|name| self.deferred.has_shader(name))
+            .collect();
+        for name in deferred_shaders {
+            let code = match self
+                .reloader
+                .as_ref()
+                .and_then(|reloader| reloader.compile(name))
+            {
+                Some(code) => code,
+                None => continue,
+            };
+            if unsafe { self.bvk.dev.device_wait_idle() }.is_err() {
+                continue;
+            }
+            if self.deferred.reload(&self.bvk, name, code).is_some() {
+                println!("[Reload] Rebuilt deferred shading for {}", name);
+            }
+        }
+        if changed.iter().any(|name| name == "pattern") {
+            let layout = self.pattern_descriptors.descriptor_set_layout;
+            if let Some(pattern_pipeline) =
+                self.reload_compute_pipeline("pattern", &self.pattern_bindings, layout)
+            {
+                let old = std::mem::replace(&mut self.pattern_pipeline, pattern_pipeline);
+                self.frames.retire(move |bvk| old.destroy(bvk));
+            }
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_graphics_pipeline(&mut self) -> Option<()> {
+        let reloader = self.reloader.as_ref()?;
+        let vert_code = reloader.compile("vertex")?;
+        let frag_code = reloader.compile("fragment")?;
+
+        //  The descriptor sets were allocated up front, so their layout can't change under us.
+        let interface = ShaderInterface::reflect(&[&vert_code, &frag_code])
+            .map_err(|e| println!("[Reload] {}", e))
+            .ok()?;
+        let bindings = interface
+            .descriptor_set()
+            .map_err(|e| println!("[Reload] {}", e))
+            .ok()?;
+        if !same_bindings(bindings, &self.uniform_bindings) {
+            println!("[Reload] Descriptor bindings changed, restart to pick them up");
+            None?;
+        }
+
+        //  Only the current variant is checked here, the rest are rebuilt as needed.
+        unsafe { self.bvk.dev.device_wait_idle().ok()? };
+        let variant = self.get_variant();
+        let pass =
+            self.graph_cache
+                .pass_info(&self.bvk, &self.render.layout(), self.render.extent)?;
+        let pipeline = VulkanPipeline::create(
+            &self.bvk,
+            &pass,
+            &vert_code,
+            &frag_code,
+            &variant,
             &[Vertex::bindings()],
             &Vertex::attributes(),
-            &[PushConstantData::push_constants()],
             &[self.uniform.descriptor_set_layout],
         )?;
+        self.pipelines.clear(&self.bvk);
+        self.offscreen_pipelines.clear(&self.bvk);
+        self.pipelines.get_or_create(&variant, || Some(pipeline));
+        self.vert_code = vert_code;
+        self.frag_code = frag_code;
+        println!("[Reload] Rebuilt graphics pipeline");
+        Some(())
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_shadow_pipeline(&mut self) -> Option<()> {
+        let reloader = self.reloader.as_ref()?;
+        let vert_code = reloader.compile("shadow")?;
+        let frag_code = reloader.compile("depth_only")?;
+        let pipeline = ShadowMap::create_pipeline(
+            &self.bvk,
+            &mut self.graph_cache,
+            self.shadow.texture.image.format,
+            &vert_code,
+            &frag_code,
+        )?;
+        let old = std::mem::replace(&mut self.shadow.pipeline, pipeline);
+        self.frames.retire(move |bvk| old.destroy(bvk));
+        println!("[Reload] Rebuilt shadow pipeline");
         Some(())
     }
+
+    //  The descriptors stay the same, so the new shader has to want the same `bindings`.
+    #[cfg(feature = "hot-reload")]
+    fn reload_compute_pipeline(
+        &self,
+        name: &str,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Option<ComputePipeline> {
+        let code = self.reloader.as_ref()?.compile(name)?;
+        let interface = ShaderInterface::reflect(&[&code])
+            .map_err(|e| println!("[Reload] {}", e))
+            .ok()?;
+        let new_bindings = interface
+            .descriptor_set()
+            .map_err(|e| println!("[Reload] {}", e))
+            .ok()?;
+        if !same_bindings(new_bindings, bindings) {
+            println!("[Reload] Descriptor bindings changed, restart to pick them up");
+            None?;
+        }
+
+        let pipeline = ComputePipeline::create(&self.bvk, &code, &[descriptor_set_layout])?;
+        println!("[Reload] Rebuilt compute pipeline {}", name);
+        Some(pipeline)
+    }
+}
+
+//  Two rings going opposite ways, one hue per light all the way around
+fn point_lights(elapsed: u128) -> Vec<PointLight> {
+    let time = elapsed as f32 / 1000.0;
+    (0..POINT_LIGHT_COUNT)
+        .map(|idx| {
+            let t = idx as f32 / POINT_LIGHT_COUNT as f32;
+            let (ring, direction) = if idx % 2 == 0 {
+                (1.6, 1.0)
+            } else {
+                (2.4, -1.0)
+            };
+            let angle = t * glm::two_pi::<f32>() + direction * time * 0.5;
+            let [r, g, b] = [0.0, 1.0, 2.0]
+                .map(|offset: f32| 0.5 + 0.5 * (glm::two_pi::<f32>() * (t + offset / 3.0)).cos());
+            PointLight {
+                position: glm::vec4(
+                    ring * angle.cos(),
+                    GROUND_HEIGHT + 0.3,
+                    ring * angle.sin(),
+                    POINT_LIGHT_RADIUS,
+                ),
+                color: glm::vec4(r, g, b, 0.0) * 2.0,
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "hot-reload")]
+fn same_bindings(
+    a: &[vk::DescriptorSetLayoutBinding],
+    b: &[vk::DescriptorSetLayoutBinding],
+) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b.iter()).all(|(a, b)| {
+            a.binding == b.binding
+                && a.descriptor_type == b.descriptor_type
+                && a.descriptor_count == b.descriptor_count
+                && a.stage_flags == b.stage_flags
+        })
 }
 
 impl Drop for VulkanPlayground {
@@ -344,17 +1353,31 @@ impl Drop for VulkanPlayground {
         unsafe {
             self.bvk.dev.device_wait_idle().unwrap();
         }
+        self.animate.destroy(&self.bvk);
+        self.animate_wave.destroy(&self.bvk);
+        self.animate_descriptors.destroy(&self.bvk);
+        self.pattern_pipeline.destroy(&self.bvk);
+        self.pattern_descriptors.destroy(&self.bvk);
+        self.pattern.destroy(&self.bvk);
         self.vbo.destroy(&mut self.bvk);
         self.ibo.destroy(&mut self.bvk);
+        self.rest_vbo.destroy(&mut self.bvk);
         self.texture.destroy(&self.bvk);
-        self.uniform.destroy(&mut self.bvk);
+        self.uniform.destroy(&self.bvk);
+        self.offscreen_uniform.destroy(&self.bvk);
         unsafe {
             self.bvk.dev.destroy_fence(self.etc_fence, None);
             self.bvk.dev.destroy_command_pool(self.cmd_pool, None);
         }
-        self.frames.destroy(&self.bvk);
-        self.pipeline.destroy(&self.bvk);
-        self.render.destroy(&self.bvk);
+        self.frames.destroy(&mut self.bvk);
+        self.transient.destroy(&mut self.bvk);
+        self.pipelines.clear(&self.bvk);
+        self.offscreen_pipelines.clear(&self.bvk);
+        self.offscreen.destroy(&self.bvk);
+        self.shadow.destroy(&self.bvk);
+        self.deferred.destroy(&self.bvk);
+        self.post.destroy(&self.bvk);
+        self.graph_cache.destroy(&self.bvk);
         self.swappy.destroy(&self.bvk);
         self.bvk.destroy();
     }
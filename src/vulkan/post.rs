@@ -0,0 +1,684 @@
+use super::*;
+use std::collections::HashMap;
+
+//  `post_lut.frag` has to agree on this
+const LUT_SIZE: usize = 16;
+
+//  In nits, for `post_hdr.frag`. Reasonable for a desktop monitor.
+const HDR_PAPER_WHITE: f32 = 200.0;
+const HDR_PEAK: f32 = 1000.0;
+
+//  One fullscreen pass of the post chain, reading the output of the one before.
+//  `params` are pushed as is, so they have to match the shader's push constant block.
+pub struct PostEffect {
+    pub name: &'static str,
+    //  The shader's name in `src/`, for hot reloading
+    pub shader: &'static str,
+    pub enabled: bool,
+    pub params: Vec<f32>,
+    frag_code: Vec<u32>,
+    //  (Into an HDR image, Into the swapchain)
+    pipelines: Option<(VulkanPipeline, VulkanPipeline)>,
+}
+
+impl PostEffect {
+    pub fn create(
+        name: &'static str,
+        shader: &'static str,
+        frag_code: &[u8],
+        params: &[f32],
+    ) -> Option<Self> {
+        Some(PostEffect {
+            name,
+            shader,
+            enabled: true,
+            params: params.to_vec(),
+            frag_code: VulkanPipeline::read_shader_code(frag_code)?,
+            pipelines: None,
+        })
+    }
+
+    //  Every effect we have, in the order they run by default.
+    pub fn builtin() -> Option<Vec<Self>> {
+        Some(vec![
+            Self::create("tonemap", "post_tonemap", shaders::POST_TONEMAP, &[1.0])?,
+            Self::create("fxaa", "post_fxaa", shaders::POST_FXAA, &[1.0, 0.125])?,
+            Self::create(
+                "vignette",
+                "post_vignette",
+                shaders::POST_VIGNETTE,
+                &[0.5, 0.8, 0.45],
+            )?,
+            Self::create("lut", "post_lut", shaders::POST_LUT, &[1.0])?,
+            Self::create("gamma", "post_gamma", shaders::POST_GAMMA, &[2.2])?,
+            Self::create(
+                "hdr",
+                "post_hdr",
+                shaders::POST_HDR,
+                &[1.0, HDR_PAPER_WHITE, HDR_PEAK, 0.0],
+            )?,
+        ])
+    }
+
+    fn destroy_pipelines(&mut self, bvk: &BabyVulkan) {
+        if let Some((to_target, to_swapchain)) = self.pipelines.take() {
+            to_target.destroy(bvk);
+            to_swapchain.destroy(bvk);
+        }
+    }
+}
+
+//  Puts the effects named in `order` first, in that order, and turns off the rest.
+//  The default chain for presenting in `color_space`.
+//  SDR tonemaps, and only does gamma by hand when the swapchain won't do it for us.
+//  HDR leaves both to `hdr`, and skips the LUT since it only covers [0, 1].
+pub fn default_effects(
+    effects: &mut [PostEffect],
+    format: vk::Format,
+    color_space: vk::ColorSpaceKHR,
+) {
+    let hdr = is_hdr(color_space);
+    for effect in effects {
+        effect.enabled = match effect.name {
+            "tonemap" | "lut" => !hdr,
+            "gamma" => !hdr && !is_srgb(format),
+            "hdr" => hdr,
+            _ => true,
+        };
+    }
+}
+
+//  `hdr` has to know which curve to encode with.
+pub fn set_output_encoding(effects: &mut [PostEffect], color_space: vk::ColorSpaceKHR) {
+    if let Some(effect) = effects.iter_mut().find(|effect| effect.name == "hdr") {
+        effect.params[3] = if color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT {
+            1.0
+        } else {
+            0.0
+        };
+    }
+}
+
+pub fn arrange_effects(effects: &mut Vec<PostEffect>, order: &[String]) {
+    let mut arranged = Vec::with_capacity(effects.len());
+    for name in order {
+        match effects.iter().position(|effect| effect.name == name) {
+            Some(idx) => {
+                let mut effect = effects.remove(idx);
+                effect.enabled = true;
+                arranged.push(effect);
+            }
+            None => println!("[Post] Unknown effect {}", name),
+        }
+    }
+    effects.iter_mut().for_each(|effect| effect.enabled = false);
+    arranged.append(effects);
+    *effects = arranged;
+}
+
+//  Effects sample whatever image the graph gave the one before them, which only changes when
+//  `GraphCache` is cleared. This is plenty for a few images.
+const MAX_INPUTS: u32 = 8;
+
+//  One effect in this frame's graph, see `PostChain::add_passes`
+struct PostPass {
+    pass: PassHandle,
+    //  Index into `effects`, `None` for `copy`
+    effect: Option<usize>,
+    input: ImageHandle,
+    //  Whether it draws into the swapchain
+    last: bool,
+    //  Filled in by `prepare`
+    descriptor_set: Option<vk::DescriptorSet>,
+}
+
+//  Runs the enabled effects one after another, starting from the scene and ending in the
+//  swapchain. Everything in between goes into HDR images from the render graph.
+pub struct PostChain {
+    pub effects: Vec<PostEffect>,
+    //  For when every effect is turned off
+    copy: PostEffect,
+    vert_code: Vec<u32>,
+    lut: Texture,
+    //  For the images from the graph, which don't come with one
+    sampler: vk::Sampler,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    //  By the view of the image they sample
+    descriptor_sets: HashMap<vk::ImageView, vk::DescriptorSet>,
+    passes: Vec<PostPass>,
+    //  (Into HDR images, Into the swapchain)
+    pass_infos: (PassInfo, PassInfo),
+}
+
+impl PostChain {
+    pub fn create(
+        bvk: &mut BabyVulkan,
+        cache: &mut GraphCache,
+        swappy: &VulkanSwapchain,
+        effects: Vec<PostEffect>,
+        fence: vk::Fence,
+        cmd_buf: vk::CommandBuffer,
+    ) -> Option<Self> {
+        let lut = Texture::create_from_pixels(
+            &create_lut_pixels(),
+            vk::Extent2D {
+                width: (LUT_SIZE * LUT_SIZE) as u32,
+                height: LUT_SIZE as u32,
+            },
+            vk::Format::R8G8B8A8_UNORM,
+            bvk,
+            fence,
+            cmd_buf,
+        )?;
+
+        //  Sampling past the edges (e.g. for FXAA) shouldn't wrap around to the other side
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe { bvk.dev.create_sampler(&sampler_info, None) }.ok()?;
+
+        //  Create Descriptor Set Layout
+        //  Every effect gets the previous output in 0 and the LUT in 1, whether it wants it or not.
+        let bindings = [0, 1].map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build()
+        });
+        let descriptor_set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+        let descriptor_set_layout = unsafe {
+            bvk.dev
+                .create_descriptor_set_layout(&descriptor_set_layout_info, None)
+        }
+        .ok()?;
+
+        //  Create Descriptor Pool
+        //  The sets themselves are allocated by `prepare`, once we know what they sample.
+        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(2 * MAX_INPUTS)
+                .build()])
+            .max_sets(MAX_INPUTS)
+            .build();
+        let descriptor_pool =
+            unsafe { bvk.dev.create_descriptor_pool(&descriptor_pool_info, None) }.ok()?;
+
+        let mut post = PostChain {
+            effects,
+            copy: PostEffect::create("copy", "post_copy", shaders::POST_COPY, &[])?,
+            vert_code: VulkanPipeline::read_shader_code(shaders::FULLSCREEN)?,
+            lut,
+            sampler,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets: HashMap::new(),
+            passes: vec![],
+            pass_infos: Self::get_pass_infos(bvk, cache, swappy)?,
+        };
+        post.create_pipelines(bvk)?;
+        Some(post)
+    }
+
+    //  The graph's images are gone after a resize, and so are the sets that sampled them.
+    //  `cache` has to be cleared first.
+    pub fn resize(
+        &mut self,
+        bvk: &BabyVulkan,
+        cache: &mut GraphCache,
+        swappy: &VulkanSwapchain,
+    ) -> Option<()> {
+        unsafe {
+            bvk.dev
+                .reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())
+                .ok()?;
+        }
+        self.descriptor_sets.clear();
+        self.passes.clear();
+        self.pass_infos = Self::get_pass_infos(bvk, cache, swappy)?;
+        self.create_pipelines(bvk)
+    }
+
+    //  Swap in a new version of `shader`, if it is one of ours.
+    //  If the pipelines can't be built, the old ones are kept.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload(&mut self, bvk: &BabyVulkan, shader: &str, code: Vec<u32>) -> Option<()> {
+        let pass_infos = self.pass_infos.clone();
+        if shader == "fullscreen" {
+            let old_code = std::mem::replace(&mut self.vert_code, code);
+            if self.create_pipelines(bvk).is_none() {
+                self.vert_code = old_code;
+                self.create_pipelines(bvk)?;
+                None?;
+            }
+            return Some(());
+        }
+        let effect = self
+            .effects
+            .iter_mut()
+            .chain(std::iter::once(&mut self.copy))
+            .find(|effect| effect.shader == shader)?;
+        let pipelines = (
+            Self::create_pipeline(
+                bvk,
+                &pass_infos.0,
+                &self.vert_code,
+                &code,
+                self.descriptor_set_layout,
+            )?,
+            Self::create_pipeline(
+                bvk,
+                &pass_infos.1,
+                &self.vert_code,
+                &code,
+                self.descriptor_set_layout,
+            )?,
+        );
+        effect.destroy_pipelines(bvk);
+        effect.pipelines = Some(pipelines);
+        effect.frag_code = code;
+        Some(())
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub fn has_shader(&self, shader: &str) -> bool {
+        shader == "fullscreen"
+            || shader == self.copy.shader
+            || self.effects.iter().any(|effect| effect.shader == shader)
+    }
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut PostEffect> {
+        self.effects.iter_mut().find(|effect| effect.name == name)
+    }
+
+    //  Adds a pass for every enabled effect to `graph`, the last one drawing into `swapchain`.
+    //  Returns what the last one wrote.
+    pub fn add_passes(
+        &mut self,
+        graph: &mut RenderGraph,
+        scene: ImageHandle,
+        swapchain: ImageHandle,
+    ) -> ImageHandle {
+        let mut enabled: Vec<Option<usize>> = self
+            .effects
+            .iter()
+            .enumerate()
+            .filter(|(_, effect)| effect.enabled)
+            .map(|(idx, _)| Some(idx))
+            .collect();
+        if enabled.is_empty() {
+            enabled.push(None);
+        }
+
+        self.passes.clear();
+        let mut input = scene;
+        for (idx, &effect) in enabled.iter().enumerate() {
+            let last = idx + 1 == enabled.len();
+            //  Still in HDR, so that nothing is lost between effects
+            let output = if last {
+                swapchain
+            } else {
+                graph.create_image(
+                    "post",
+                    ImageDesc::color(
+                        HDR_FORMAT,
+                        self.pass_infos.0.extent,
+                        vk::SampleCountFlags::TYPE_1,
+                    ),
+                )
+            };
+            let name = match effect {
+                Some(effect) => self.effects[effect].name,
+                None => self.copy.name,
+            };
+            let mut pass = graph.add_pass(name, PassKind::Graphics);
+            pass.sample(input, vk::PipelineStageFlags::FRAGMENT_SHADER);
+            //  Every pixel is drawn over, so the old contents don't matter.
+            let output = pass.color(output, LoadOp::DontCare);
+            self.passes.push(PostPass {
+                pass: pass.handle(),
+                effect,
+                input,
+                last,
+                descriptor_set: None,
+            });
+            input = output;
+        }
+        input
+    }
+
+    //  Find the descriptor sets for the images `compiled` gives the passes from `add_passes`.
+    pub fn prepare(&mut self, bvk: &BabyVulkan, compiled: &CompiledGraph) -> Option<()> {
+        for idx in 0..self.passes.len() {
+            if !compiled.runs(self.passes[idx].pass) {
+                continue;
+            }
+            let view = compiled.view(self.passes[idx].input)?;
+            let descriptor_set = match self.descriptor_sets.get(&view) {
+                Some(&descriptor_set) => descriptor_set,
+                None => {
+                    let descriptor_set = self.create_descriptor_set(bvk, view)?;
+                    self.descriptor_sets.insert(view, descriptor_set);
+                    descriptor_set
+                }
+            };
+            self.passes[idx].descriptor_set = Some(descriptor_set);
+        }
+        Some(())
+    }
+
+    //  Record `pass` into `cmd_buf`, if it is one of ours
+    pub fn record(&self, bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer, pass: PassHandle) {
+        let post_pass = match self.passes.iter().find(|post_pass| post_pass.pass == pass) {
+            Some(post_pass) => post_pass,
+            None => return,
+        };
+        let effect = match post_pass.effect {
+            Some(effect) => &self.effects[effect],
+            None => &self.copy,
+        };
+        let (pipeline, descriptor_set) = match (&effect.pipelines, post_pass.descriptor_set) {
+            (Some((_, to_swapchain)), Some(descriptor_set)) if post_pass.last => {
+                (to_swapchain, descriptor_set)
+            }
+            (Some((to_target, _)), Some(descriptor_set)) => (to_target, descriptor_set),
+            _ => return,
+        };
+        unsafe {
+            bvk.dev
+                .cmd_bind_pipeline(cmd_buf, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline);
+            bvk.dev.cmd_bind_descriptor_sets(
+                cmd_buf,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            cmd_push_constants(
+                bvk,
+                cmd_buf,
+                pipeline.pipeline_layout,
+                &pipeline.push_constant_ranges,
+                std::slice::from_raw_parts(
+                    effect.params.as_ptr() as *const u8,
+                    std::mem::size_of_val(effect.params.as_slice()),
+                ),
+            );
+            bvk.dev.cmd_draw(cmd_buf, 3, 1, 0, 0);
+        }
+    }
+
+    fn get_pass_infos(
+        bvk: &BabyVulkan,
+        cache: &mut GraphCache,
+        swappy: &VulkanSwapchain,
+    ) -> Option<(PassInfo, PassInfo)> {
+        let layout = |format| PassLayout {
+            colors: vec![format],
+            depth: None,
+            samples: vk::SampleCountFlags::TYPE_1,
+            resolve: false,
+        };
+        Some((
+            cache.pass_info(bvk, &layout(HDR_FORMAT), swappy.extent)?,
+            cache.pass_info(bvk, &layout(swappy.format), swappy.extent)?,
+        ))
+    }
+
+    fn create_descriptor_set(
+        &self,
+        bvk: &BabyVulkan,
+        input: vk::ImageView,
+    ) -> Option<vk::DescriptorSet> {
+        let descriptor_sets_info = vk::DescriptorSetAllocateInfo::builder()
+            .set_layouts(&[self.descriptor_set_layout])
+            .descriptor_pool(self.descriptor_pool)
+            .build();
+        let descriptor_set =
+            match unsafe { bvk.dev.allocate_descriptor_sets(&descriptor_sets_info) } {
+                Ok(descriptor_sets) => descriptor_sets.into_iter().next()?,
+                Err(_) => {
+                    println!(
+                        "[Post] Out of descriptor sets, more than {} inputs",
+                        MAX_INPUTS
+                    );
+                    None?
+                }
+            };
+
+        let input_info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(input)
+            .sampler(self.sampler)
+            .build();
+        let lut_info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.lut.image_view)
+            .sampler(self.lut.sampler)
+            .build();
+        let writes = [(0, &input_info), (1, &lut_info)].map(|(binding, info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(binding)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(std::slice::from_ref(info))
+                .build()
+        });
+        unsafe { bvk.dev.update_descriptor_sets(&writes, &[]) }
+        Some(descriptor_set)
+    }
+
+    fn create_pipelines(&mut self, bvk: &BabyVulkan) -> Option<()> {
+        let (to_target, to_swapchain) = self.pass_infos.clone();
+        for effect in self
+            .effects
+            .iter_mut()
+            .chain(std::iter::once(&mut self.copy))
+        {
+            effect.destroy_pipelines(bvk);
+            let pipelines = (
+                Self::create_pipeline(
+                    bvk,
+                    &to_target,
+                    &self.vert_code,
+                    &effect.frag_code,
+                    self.descriptor_set_layout,
+                )?,
+                Self::create_pipeline(
+                    bvk,
+                    &to_swapchain,
+                    &self.vert_code,
+                    &effect.frag_code,
+                    self.descriptor_set_layout,
+                )?,
+            );
+            //  A mismatch would push garbage, or read past the end of `params`
+            let expected = pipelines
+                .0
+                .push_constant_ranges
+                .iter()
+                .map(|range| range.offset + range.size)
+                .max()
+                .unwrap_or(0) as usize;
+            let given = std::mem::size_of_val(effect.params.as_slice());
+            effect.pipelines = Some(pipelines);
+            if expected != given {
+                println!(
+                    "[Post] {}.frag takes {} bytes of parameters, but {} has {}",
+                    effect.shader, expected, effect.name, given
+                );
+                None?;
+            }
+        }
+        Some(())
+    }
+
+    fn create_pipeline(
+        bvk: &BabyVulkan,
+        pass: &PassInfo,
+        vert_code: &[u32],
+        frag_code: &[u32],
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Option<VulkanPipeline> {
+        //  Only what's in `descriptor_set_layout` can be given to the shaders
+        let interface = ShaderInterface::reflect(&[vert_code, frag_code])
+            .map_err(|e| println!("[Post] {}", e))
+            .ok()?;
+        let bindings = interface
+            .descriptor_set()
+            .map_err(|e| println!("[Post] {}", e))
+            .ok()?;
+        if let Some(binding) = bindings.iter().find(|binding| {
+            binding.binding > 1
+                || binding.descriptor_type != vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        }) {
+            println!(
+                "[Post] Effects can only sample bindings 0 and 1, not {} ({:?})",
+                binding.binding, binding.descriptor_type
+            );
+            None?;
+        }
+        VulkanPipeline::create(
+            bvk,
+            pass,
+            vert_code,
+            frag_code,
+            &PipelineVariant::default(),
+            &[],
+            &[],
+            &[descriptor_set_layout],
+        )
+    }
+
+    pub fn destroy(&mut self, bvk: &BabyVulkan) {
+        self.effects
+            .iter_mut()
+            .chain(std::iter::once(&mut self.copy))
+            .for_each(|effect| effect.destroy_pipelines(bvk));
+        unsafe {
+            bvk.dev.destroy_sampler(self.sampler, None);
+            bvk.dev.destroy_descriptor_pool(self.descriptor_pool, None);
+            bvk.dev
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        self.lut.destroy(bvk);
+    }
+}
+
+//  A gentle teal and orange grade, just so that there is something to see.
+//  Shadows are pushed towards teal and highlights towards orange.
+fn create_lut_pixels() -> Vec<u8> {
+    let max = (LUT_SIZE - 1) as f32;
+    let mut pixels = vec![0; LUT_SIZE * LUT_SIZE * LUT_SIZE * 4];
+    for b in 0..LUT_SIZE {
+        for g in 0..LUT_SIZE {
+            for r in 0..LUT_SIZE {
+                let color = [r as f32 / max, g as f32 / max, b as f32 / max];
+                let luma = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+                let shift = (luma - 0.5) * 0.2;
+                let graded = [color[0] + shift, color[1] + shift * 0.25, color[2] - shift];
+                let idx = (g * LUT_SIZE * LUT_SIZE + b * LUT_SIZE + r) * 4;
+                for (pixel, channel) in pixels[idx..idx + 3].iter_mut().zip(graded) {
+                    *pixel = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+                pixels[idx + 3] = 255;
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_params_match_shaders() {
+        let vert_code = load_spirv(shaders::FULLSCREEN).unwrap();
+        let copy = PostEffect::create("copy", "post_copy", shaders::POST_COPY, &[]).unwrap();
+        for effect in PostEffect::builtin().unwrap().iter().chain([&copy]) {
+            let interface = ShaderInterface::reflect(&[&vert_code, &effect.frag_code]).unwrap();
+            let size = interface
+                .push_constants
+                .iter()
+                .map(|range| range.offset + range.size)
+                .max()
+                .unwrap_or(0) as usize;
+            assert_eq!(size, effect.params.len() * 4, "{}", effect.name);
+            assert!(
+                interface
+                    .descriptor_set()
+                    .unwrap()
+                    .iter()
+                    .all(|binding| binding.binding <= 1),
+                "{}",
+                effect.name
+            );
+        }
+    }
+
+    #[test]
+    fn default_effects_for_hdr() {
+        let enabled = |format, color_space| {
+            let mut effects = PostEffect::builtin().unwrap();
+            default_effects(&mut effects, format, color_space);
+            effects
+                .iter()
+                .filter(|effect| effect.enabled)
+                .map(|effect| effect.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            enabled(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+            ["tonemap", "fxaa", "vignette", "lut"]
+        );
+        assert_eq!(
+            enabled(
+                vk::Format::B8G8R8A8_UNORM,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR
+            ),
+            ["tonemap", "fxaa", "vignette", "lut", "gamma"]
+        );
+        assert_eq!(
+            enabled(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::HDR10_ST2084_EXT
+            ),
+            ["fxaa", "vignette", "hdr"]
+        );
+    }
+
+    #[test]
+    fn arrange_effects_by_name() {
+        let mut effects = PostEffect::builtin().unwrap();
+        arrange_effects(&mut effects, &["vignette".to_owned(), "tonemap".to_owned()]);
+        let order: Vec<(&str, bool)> = effects
+            .iter()
+            .map(|effect| (effect.name, effect.enabled))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("vignette", true),
+                ("tonemap", true),
+                ("fxaa", false),
+                ("lut", false),
+                ("gamma", false),
+                ("hdr", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1030 @@
+use super::*;
+use std::collections::HashMap;
+
+//  A tiny SPIR-V reflector.
+//  It only understands the handful of instructions needed to figure out what a shader expects from
+//  us: descriptor bindings, push constants, and vertex inputs.
+//  All the magic numbers come from https://registry.khronos.org/SPIR-V/specs/unified1/SPIRV.html.
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+//  Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT_TRUE: u32 = 48;
+const OP_SPEC_CONSTANT_FALSE: u32 = 49;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+//  Decorations
+const DECORATION_SPEC_ID: u32 = 1;
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+//  Execution Modes
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+//  Storage Classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+//  Image Dimensions
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug)]
+pub enum ReflectError {
+    InvalidSpirv(&'static str),
+    Unsupported(String),
+    ConflictingBinding {
+        set: u32,
+        binding: u32,
+    },
+    MissingVertexAttribute {
+        name: String,
+        location: u32,
+        format: vk::Format,
+    },
+    MismatchedVertexAttribute {
+        name: String,
+        location: u32,
+        shader: vk::Format,
+        rust: vk::Format,
+    },
+}
+
+impl std::fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectError::InvalidSpirv(why) => write!(f, "Invalid SPIR-V: {}", why),
+            ReflectError::Unsupported(what) => write!(f, "Unsupported shader interface: {}", what),
+            ReflectError::ConflictingBinding { set, binding } => write!(
+                f,
+                "Shader stages disagree on the type of set = {}, binding = {}",
+                set, binding
+            ),
+            ReflectError::MissingVertexAttribute {
+                name,
+                location,
+                format,
+            } => write!(
+                f,
+                "Vertex input `{}` (location = {}, {:?}) is missing from the Rust vertex layout",
+                name, location, format
+            ),
+            ReflectError::MismatchedVertexAttribute {
+                name,
+                location,
+                shader,
+                rust,
+            } => write!(
+                f,
+                "Vertex input `{}` (location = {}) is {:?} in the shader but {:?} in the Rust vertex layout",
+                name, location, shader, rust
+            ),
+        }
+    }
+}
+
+pub struct VertexInput {
+    pub name: String,
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+//  Everything the Rust side needs to know about a set of shader stages.
+pub struct ShaderInterface {
+    //  Indexed by set number.
+    pub descriptor_sets: Vec<Vec<vk::DescriptorSetLayoutBinding>>,
+    pub push_constants: Vec<vk::PushConstantRange>,
+    pub vertex_inputs: Vec<VertexInput>,
+    //  `layout(local_size_x = ...) in;` of a compute shader.
+    pub local_size: [u32; 3],
+}
+
+impl ShaderInterface {
+    pub fn reflect(stages: &[&[u32]]) -> Result<Self, ReflectError> {
+        let defaults = Specialization::default();
+        let stages: Vec<(&[u32], &Specialization)> =
+            stages.iter().map(|code| (*code, &defaults)).collect();
+        Self::reflect_specialized(&stages)
+    }
+
+    //  Specialization constants (like the length of `lights[LIGHT_COUNT]`) take their value from
+    //  each stage's `Specialization`, falling back to the default in the shader.
+    pub fn reflect_specialized(stages: &[(&[u32], &Specialization)]) -> Result<Self, ReflectError> {
+        let mut interface = ShaderInterface {
+            descriptor_sets: vec![],
+            push_constants: vec![],
+            vertex_inputs: vec![],
+            local_size: [1, 1, 1],
+        };
+
+        for (code, specialization) in stages {
+            let module = SpirvModule::parse(code, specialization)?;
+
+            //  Descriptors used by multiple stages are merged into a single binding.
+            for (set, binding) in module.descriptor_bindings()? {
+                let set_idx = set as usize;
+                if interface.descriptor_sets.len() <= set_idx {
+                    interface.descriptor_sets.resize(set_idx + 1, vec![]);
+                }
+                let bindings = &mut interface.descriptor_sets[set_idx];
+                match bindings.iter_mut().find(|b| b.binding == binding.binding) {
+                    Some(existing) => {
+                        if existing.descriptor_type != binding.descriptor_type
+                            || existing.descriptor_count != binding.descriptor_count
+                        {
+                            Err(ReflectError::ConflictingBinding {
+                                set,
+                                binding: binding.binding,
+                            })?;
+                        }
+                        existing.stage_flags |= binding.stage_flags;
+                    }
+                    None => bindings.push(binding),
+                }
+            }
+
+            //  Every byte has to be pushed with all of the stages whose range covers it, so
+            //  overlapping ranges are merged into one that is visible to all of their stages.
+            if let Some(mut range) = module.push_constant_range()? {
+                while let Some(idx) = interface.push_constants.iter().position(|r| {
+                    r.offset < range.offset + range.size && range.offset < r.offset + r.size
+                }) {
+                    let other = interface.push_constants.swap_remove(idx);
+                    let end = (range.offset + range.size).max(other.offset + other.size);
+                    range.offset = range.offset.min(other.offset);
+                    range.size = end - range.offset;
+                    range.stage_flags |= other.stage_flags;
+                }
+                interface.push_constants.push(range);
+            }
+
+            if module.stage == vk::ShaderStageFlags::VERTEX {
+                interface.vertex_inputs = module.vertex_inputs()?;
+            }
+            if module.stage == vk::ShaderStageFlags::COMPUTE {
+                interface.local_size = module.local_size;
+            }
+        }
+
+        interface
+            .descriptor_sets
+            .iter_mut()
+            .for_each(|bindings| bindings.sort_by_key(|b| b.binding));
+        interface.push_constants.sort_by_key(|r| r.offset);
+        Ok(interface)
+    }
+
+    //  Everything we build only has room for a single descriptor set, so make sure that's all
+    //  the shaders want.
+    pub fn descriptor_set(&self) -> Result<&[vk::DescriptorSetLayoutBinding], ReflectError> {
+        match self.descriptor_sets.as_slice() {
+            [] => Err(ReflectError::Unsupported(String::from(
+                "shader doesn't use any descriptors",
+            ))),
+            [bindings] => Ok(bindings),
+            sets => Err(ReflectError::Unsupported(format!(
+                "shader uses {} descriptor sets, only set = 0 is supported",
+                sets.len()
+            ))),
+        }
+    }
+
+    //  Make sure that every input the vertex shader reads is provided with the right format.
+    pub fn check_vertex_attributes(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<(), ReflectError> {
+        self.vertex_inputs.iter().try_for_each(|input| {
+            match attributes.iter().find(|a| a.location == input.location) {
+                None => Err(ReflectError::MissingVertexAttribute {
+                    name: input.name.clone(),
+                    location: input.location,
+                    format: input.format,
+                }),
+                Some(attribute) if attribute.format != input.format => {
+                    Err(ReflectError::MismatchedVertexAttribute {
+                        name: input.name.clone(),
+                        location: input.location,
+                        shader: input.format,
+                        rust: attribute.format,
+                    })
+                }
+                Some(_) => Ok(()),
+            }
+        })
+    }
+}
+
+//  SPIR-V is a stream of u32 words, but `include_bytes!` only promises u8 alignment.
+//  Copy the words out one by one instead of casting the pointer, and check that this actually
+//  looks like SPIR-V before handing it to the driver.
+pub fn load_spirv(bytes: &[u8]) -> Result<Vec<u32>, ReflectError> {
+    if bytes.len() % 4 != 0 {
+        Err(ReflectError::InvalidSpirv("size is not a multiple of 4"))?;
+    }
+    //  Magic, Version, Generator, Bound, Schema
+    if bytes.len() < 5 * 4 {
+        Err(ReflectError::InvalidSpirv("too short for a header"))?;
+    }
+    let mut words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+
+    //  Modules may have been written with the other endianness.
+    if words[0] == SPIRV_MAGIC.swap_bytes() {
+        words.iter_mut().for_each(|word| *word = word.swap_bytes());
+    }
+    if words[0] != SPIRV_MAGIC {
+        Err(ReflectError::InvalidSpirv("bad magic number"))?;
+    }
+    Ok(words)
+}
+
+enum SpirvType {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+struct SpirvVariable {
+    id: u32,
+    ty: u32,
+    storage: u32,
+}
+
+struct SpirvModule {
+    stage: vk::ShaderStageFlags,
+    local_size: [u32; 3],
+    names: HashMap<u32, String>,
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    //  (id, decoration) -> first literal
+    decorations: HashMap<(u32, u32), u32>,
+    //  (struct, member, decoration) -> first literal
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    variables: Vec<SpirvVariable>,
+}
+
+impl SpirvModule {
+    fn parse(code: &[u32], specialization: &Specialization) -> Result<Self, ReflectError> {
+        if code.len() < 5 || code[0] != SPIRV_MAGIC {
+            Err(ReflectError::InvalidSpirv("bad header"))?;
+        }
+
+        let mut module = SpirvModule {
+            stage: vk::ShaderStageFlags::empty(),
+            local_size: [1, 1, 1],
+            names: HashMap::new(),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            variables: vec![],
+        };
+
+        //  (id, default value), resolved once all of the decorations are known.
+        let mut spec_constants = vec![];
+
+        //  Skip the header, then walk through each instruction.
+        //  The first word of each is `word_count << 16 | opcode`.
+        let mut idx = 5;
+        while idx < code.len() {
+            let word_count = (code[idx] >> 16) as usize;
+            let opcode = code[idx] & 0xffff;
+            if word_count == 0 || idx + word_count > code.len() {
+                Err(ReflectError::InvalidSpirv("truncated instruction"))?;
+            }
+            let ops = &code[idx + 1..idx + word_count];
+            let op = |i: usize| {
+                ops.get(i)
+                    .copied()
+                    .ok_or(ReflectError::InvalidSpirv("missing operand"))
+            };
+
+            match opcode {
+                OP_NAME => {
+                    module.names.insert(op(0)?, parse_string(&ops[1..]));
+                }
+                OP_ENTRY_POINT if module.stage.is_empty() => {
+                    module.stage = match op(0)? {
+                        0 => vk::ShaderStageFlags::VERTEX,
+                        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+                        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                        3 => vk::ShaderStageFlags::GEOMETRY,
+                        4 => vk::ShaderStageFlags::FRAGMENT,
+                        5 => vk::ShaderStageFlags::COMPUTE,
+                        model => Err(ReflectError::Unsupported(format!(
+                            "execution model {}",
+                            model
+                        )))?,
+                    };
+                }
+                OP_EXECUTION_MODE if op(1)? == EXECUTION_MODE_LOCAL_SIZE => {
+                    module.local_size = [op(2)?, op(3)?, op(4)?];
+                }
+                OP_TYPE_BOOL => {
+                    module.types.insert(op(0)?, SpirvType::Bool);
+                }
+                OP_TYPE_INT => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Int {
+                            width: op(1)?,
+                            signed: op(2)? == 1,
+                        },
+                    );
+                }
+                OP_TYPE_FLOAT => {
+                    module
+                        .types
+                        .insert(op(0)?, SpirvType::Float { width: op(1)? });
+                }
+                OP_TYPE_VECTOR => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Vector {
+                            component: op(1)?,
+                            count: op(2)?,
+                        },
+                    );
+                }
+                OP_TYPE_MATRIX => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Matrix {
+                            column: op(1)?,
+                            count: op(2)?,
+                        },
+                    );
+                }
+                OP_TYPE_IMAGE => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Image {
+                            dim: op(2)?,
+                            sampled: op(6)?,
+                        },
+                    );
+                }
+                OP_TYPE_SAMPLER => {
+                    module.types.insert(op(0)?, SpirvType::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    module.types.insert(op(0)?, SpirvType::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Array {
+                            element: op(1)?,
+                            length: op(2)?,
+                        },
+                    );
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(op(0)?, SpirvType::RuntimeArray);
+                }
+                OP_TYPE_STRUCT => {
+                    module.types.insert(
+                        op(0)?,
+                        SpirvType::Struct {
+                            members: ops[1..].to_vec(),
+                        },
+                    );
+                }
+                OP_TYPE_POINTER => {
+                    module
+                        .types
+                        .insert(op(0)?, SpirvType::Pointer { pointee: op(2)? });
+                }
+                OP_CONSTANT => {
+                    module.constants.insert(op(1)?, op(2)?);
+                }
+                OP_SPEC_CONSTANT => spec_constants.push((op(1)?, op(2)?)),
+                OP_SPEC_CONSTANT_TRUE => spec_constants.push((op(1)?, vk::TRUE)),
+                OP_SPEC_CONSTANT_FALSE => spec_constants.push((op(1)?, vk::FALSE)),
+                OP_VARIABLE => module.variables.push(SpirvVariable {
+                    ty: op(0)?,
+                    id: op(1)?,
+                    storage: op(2)?,
+                }),
+                OP_DECORATE => {
+                    module
+                        .decorations
+                        .insert((op(0)?, op(1)?), op(2).unwrap_or(0));
+                }
+                OP_MEMBER_DECORATE => {
+                    module
+                        .member_decorations
+                        .insert((op(0)?, op(1)?, op(2)?), op(3).unwrap_or(0));
+                }
+                _ => {}
+            }
+            idx += word_count;
+        }
+
+        if module.stage.is_empty() {
+            Err(ReflectError::InvalidSpirv("no entry point"))?;
+        }
+
+        //  Spec constants are plain constants once we know their final value.
+        for (id, default) in spec_constants {
+            let value = module
+                .decorations
+                .get(&(id, DECORATION_SPEC_ID))
+                .and_then(|spec_id| specialization.get(*spec_id))
+                .unwrap_or(default);
+            module.constants.insert(id, value);
+        }
+        Ok(module)
+    }
+
+    fn descriptor_bindings(
+        &self,
+    ) -> Result<Vec<(u32, vk::DescriptorSetLayoutBinding)>, ReflectError> {
+        self.variables
+            .iter()
+            .filter(|var| {
+                [
+                    STORAGE_UNIFORM_CONSTANT,
+                    STORAGE_UNIFORM,
+                    STORAGE_STORAGE_BUFFER,
+                ]
+                .contains(&var.storage)
+            })
+            .map(|var| {
+                let binding = self
+                    .decorations
+                    .get(&(var.id, DECORATION_BINDING))
+                    .copied()
+                    .ok_or_else(|| {
+                        ReflectError::Unsupported(format!("`{}` has no binding", self.name(var.id)))
+                    })?;
+                let set = self
+                    .decorations
+                    .get(&(var.id, DECORATION_DESCRIPTOR_SET))
+                    .copied()
+                    .unwrap_or(0);
+
+                //  Arrays of descriptors take up `length` slots in one binding.
+                let mut ty = self.pointee(var.ty)?;
+                let mut count = 1;
+                loop {
+                    match self.types.get(&ty) {
+                        Some(SpirvType::Array { element, length }) => {
+                            count *= self.constant(*length)?;
+                            ty = *element;
+                        }
+                        Some(SpirvType::RuntimeArray) => Err(ReflectError::Unsupported(format!(
+                            "`{}` is an unsized descriptor array",
+                            self.name(var.id)
+                        )))?,
+                        _ => break,
+                    }
+                }
+
+                let descriptor_type = match (var.storage, self.types.get(&ty)) {
+                    (STORAGE_UNIFORM_CONSTANT, Some(SpirvType::SampledImage)) => {
+                        vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+                    }
+                    (STORAGE_UNIFORM_CONSTANT, Some(SpirvType::Sampler)) => {
+                        vk::DescriptorType::SAMPLER
+                    }
+                    (STORAGE_UNIFORM_CONSTANT, Some(SpirvType::Image { dim, sampled })) => {
+                        match (*dim, *sampled) {
+                            (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                            (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                            (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                            (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                            _ => vk::DescriptorType::SAMPLED_IMAGE,
+                        }
+                    }
+                    (STORAGE_UNIFORM, Some(SpirvType::Struct { .. }))
+                        if self
+                            .decorations
+                            .contains_key(&(ty, DECORATION_BUFFER_BLOCK)) =>
+                    {
+                        vk::DescriptorType::STORAGE_BUFFER
+                    }
+                    (STORAGE_UNIFORM, Some(SpirvType::Struct { .. }))
+                        if self.decorations.contains_key(&(ty, DECORATION_BLOCK)) =>
+                    {
+                        vk::DescriptorType::UNIFORM_BUFFER
+                    }
+                    (STORAGE_STORAGE_BUFFER, Some(SpirvType::Struct { .. })) => {
+                        vk::DescriptorType::STORAGE_BUFFER
+                    }
+                    _ => Err(ReflectError::Unsupported(format!(
+                        "`{}` has an unknown descriptor type",
+                        self.name(var.id)
+                    )))?,
+                };
+
+                Ok((
+                    set,
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(binding)
+                        .descriptor_type(descriptor_type)
+                        .descriptor_count(count)
+                        .stage_flags(self.stage)
+                        .build(),
+                ))
+            })
+            .collect()
+    }
+
+    fn push_constant_range(&self) -> Result<Option<vk::PushConstantRange>, ReflectError> {
+        let var = match self
+            .variables
+            .iter()
+            .find(|var| var.storage == STORAGE_PUSH_CONSTANT)
+        {
+            Some(var) => var,
+            None => return Ok(None),
+        };
+
+        //  Only the members this stage declares count towards its range.
+        let ty = self.pointee(var.ty)?;
+        let members = match self.types.get(&ty) {
+            Some(SpirvType::Struct { members }) => members,
+            _ => Err(ReflectError::InvalidSpirv(
+                "push constant block is not a struct",
+            ))?,
+        };
+        let mut start = u32::MAX;
+        let mut end = 0;
+        for (idx, &member) in members.iter().enumerate() {
+            let offset = self.member_offset(ty, idx as u32);
+            start = start.min(offset);
+            end = end.max(offset + self.member_size(ty, idx as u32, member)?);
+        }
+        if members.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            vk::PushConstantRange::builder()
+                .offset(start)
+                .size(end - start)
+                .stage_flags(self.stage)
+                .build(),
+        ))
+    }
+
+    fn vertex_inputs(&self) -> Result<Vec<VertexInput>, ReflectError> {
+        let mut inputs = self
+            .variables
+            .iter()
+            .filter(|var| {
+                var.storage == STORAGE_INPUT
+                    && !self
+                        .decorations
+                        .contains_key(&(var.id, DECORATION_BUILT_IN))
+            })
+            .map(|var| {
+                let location = self
+                    .decorations
+                    .get(&(var.id, DECORATION_LOCATION))
+                    .copied()
+                    .ok_or_else(|| {
+                        ReflectError::Unsupported(format!(
+                            "`{}` has no location",
+                            self.name(var.id)
+                        ))
+                    })?;
+                let ty = self.pointee(var.ty)?;
+                let (component, count) = match self.types.get(&ty) {
+                    Some(SpirvType::Vector { component, count }) => (*component, *count),
+                    _ => (ty, 1),
+                };
+                let format = match (self.types.get(&component), count) {
+                    (Some(SpirvType::Float { width: 32 }), 1) => vk::Format::R32_SFLOAT,
+                    (Some(SpirvType::Float { width: 32 }), 2) => vk::Format::R32G32_SFLOAT,
+                    (Some(SpirvType::Float { width: 32 }), 3) => vk::Format::R32G32B32_SFLOAT,
+                    (Some(SpirvType::Float { width: 32 }), 4) => vk::Format::R32G32B32A32_SFLOAT,
+                    (Some(SpirvType::Int { width: 32, signed }), 1) => {
+                        if *signed {
+                            vk::Format::R32_SINT
+                        } else {
+                            vk::Format::R32_UINT
+                        }
+                    }
+                    (Some(SpirvType::Int { width: 32, signed }), 2) => {
+                        if *signed {
+                            vk::Format::R32G32_SINT
+                        } else {
+                            vk::Format::R32G32_UINT
+                        }
+                    }
+                    (Some(SpirvType::Int { width: 32, signed }), 3) => {
+                        if *signed {
+                            vk::Format::R32G32B32_SINT
+                        } else {
+                            vk::Format::R32G32B32_UINT
+                        }
+                    }
+                    (Some(SpirvType::Int { width: 32, signed }), 4) => {
+                        if *signed {
+                            vk::Format::R32G32B32A32_SINT
+                        } else {
+                            vk::Format::R32G32B32A32_UINT
+                        }
+                    }
+                    _ => Err(ReflectError::Unsupported(format!(
+                        "vertex input `{}` has an unknown format",
+                        self.name(var.id)
+                    )))?,
+                };
+                Ok(VertexInput {
+                    name: self.name(var.id),
+                    location,
+                    format,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        inputs.sort_by_key(|input| input.location);
+        Ok(inputs)
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("%{}", id))
+    }
+
+    fn pointee(&self, ty: u32) -> Result<u32, ReflectError> {
+        match self.types.get(&ty) {
+            Some(SpirvType::Pointer { pointee }) => Ok(*pointee),
+            _ => Err(ReflectError::InvalidSpirv("variable is not a pointer")),
+        }
+    }
+
+    fn constant(&self, id: u32) -> Result<u32, ReflectError> {
+        self.constants
+            .get(&id)
+            .copied()
+            .ok_or(ReflectError::Unsupported(String::from(
+                "array length is not a constant or a plain specialization constant",
+            )))
+    }
+
+    fn member_offset(&self, ty: u32, member: u32) -> u32 {
+        self.member_decorations
+            .get(&(ty, member, DECORATION_OFFSET))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    //  Matrices and arrays need the strides from their decorations.
+    fn member_size(&self, parent: u32, member: u32, ty: u32) -> Result<u32, ReflectError> {
+        match self.types.get(&ty) {
+            Some(SpirvType::Matrix { count, .. }) => {
+                match self
+                    .member_decorations
+                    .get(&(parent, member, DECORATION_MATRIX_STRIDE))
+                {
+                    Some(stride) => Ok(stride * count),
+                    None => self.size_of(ty),
+                }
+            }
+            _ => self.size_of(ty),
+        }
+    }
+
+    fn size_of(&self, ty: u32) -> Result<u32, ReflectError> {
+        match self.types.get(&ty) {
+            Some(SpirvType::Bool) => Ok(4),
+            Some(SpirvType::Int { width, .. }) | Some(SpirvType::Float { width }) => Ok(width / 8),
+            Some(SpirvType::Vector { component, count }) => Ok(self.size_of(*component)? * count),
+            Some(SpirvType::Matrix { column, count }) => Ok(self.size_of(*column)? * count),
+            Some(SpirvType::Array { element, length }) => {
+                let stride = match self.decorations.get(&(ty, DECORATION_ARRAY_STRIDE)) {
+                    Some(stride) => *stride,
+                    None => self.size_of(*element)?,
+                };
+                Ok(stride * self.constant(*length)?)
+            }
+            Some(SpirvType::Struct { members }) => {
+                members
+                    .iter()
+                    .enumerate()
+                    .try_fold(0, |end, (idx, &member)| {
+                        let idx = idx as u32;
+                        Ok(end
+                            .max(self.member_offset(ty, idx) + self.member_size(ty, idx, member)?))
+                    })
+            }
+            _ => Err(ReflectError::Unsupported(String::from(
+                "push constant member has an unknown size",
+            ))),
+        }
+    }
+}
+
+//  Strings are packed little endian and null terminated.
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        stage_flags: vk::ShaderStageFlags,
+    ) -> (u32, vk::DescriptorType, u32, vk::ShaderStageFlags) {
+        (binding, descriptor_type, 1, stage_flags)
+    }
+
+    fn bindings(
+        interface: &ShaderInterface,
+    ) -> Vec<(u32, vk::DescriptorType, u32, vk::ShaderStageFlags)> {
+        interface
+            .descriptor_set()
+            .unwrap()
+            .iter()
+            .map(|b| {
+                (
+                    b.binding,
+                    b.descriptor_type,
+                    b.descriptor_count,
+                    b.stage_flags,
+                )
+            })
+            .collect()
+    }
+
+    fn push_constants(interface: &ShaderInterface) -> Vec<(u32, u32, vk::ShaderStageFlags)> {
+        interface
+            .push_constants
+            .iter()
+            .map(|r| (r.offset, r.size, r.stage_flags))
+            .collect()
+    }
+
+    #[test]
+    fn reflect_graphics_shaders() {
+        let vert_code = load_spirv(shaders::VERTEX).unwrap();
+        let frag_code = load_spirv(shaders::FRAGMENT).unwrap();
+        let interface = ShaderInterface::reflect(&[&vert_code, &frag_code]).unwrap();
+
+        assert_eq!(
+            bindings(&interface),
+            [
+                binding(
+                    0,
+                    vk::DescriptorType::UNIFORM_BUFFER,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+                ),
+                binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT
+                ),
+                binding(
+                    2,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT
+                ),
+                binding(
+                    3,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT
+                ),
+                binding(
+                    4,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT
+                ),
+            ]
+        );
+        assert_eq!(
+            push_constants(&interface),
+            [(0, 128, vk::ShaderStageFlags::VERTEX)]
+        );
+
+        let inputs: Vec<(u32, vk::Format)> = interface
+            .vertex_inputs
+            .iter()
+            .map(|input| (input.location, input.format))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                (0, vk::Format::R32G32B32_SFLOAT),
+                (1, vk::Format::R32G32B32_SFLOAT),
+                (2, vk::Format::R32G32_SFLOAT),
+                (3, vk::Format::R32G32B32_SFLOAT),
+            ]
+        );
+        assert!(interface
+            .check_vertex_attributes(&Vertex::attributes())
+            .is_ok());
+        assert!(matches!(
+            interface.check_vertex_attributes(&Vertex::attributes()[..2]),
+            Err(ReflectError::MissingVertexAttribute { location: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn reflect_animate_shader() {
+        //  Both permutations have to fit the same `ComputeDescriptors`.
+        for bytes in [shaders::ANIMATE, shaders::ANIMATE_WAVE] {
+            reflect_animate_permutation(bytes);
+        }
+    }
+
+    fn reflect_animate_permutation(bytes: &[u8]) {
+        let code = load_spirv(bytes).unwrap();
+        let interface = ShaderInterface::reflect(&[&code]).unwrap();
+
+        assert_eq!(
+            bindings(&interface),
+            [
+                binding(
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE
+                ),
+                binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE
+                ),
+            ]
+        );
+        assert_eq!(
+            push_constants(&interface),
+            [(0, 8, vk::ShaderStageFlags::COMPUTE)]
+        );
+        assert!(interface.vertex_inputs.is_empty());
+        assert_eq!(interface.local_size, [64, 1, 1]);
+    }
+
+    #[test]
+    fn reflect_pattern_shader() {
+        let code = load_spirv(shaders::PATTERN).unwrap();
+        let interface = ShaderInterface::reflect(&[&code]).unwrap();
+
+        assert_eq!(
+            bindings(&interface),
+            [binding(
+                0,
+                vk::DescriptorType::STORAGE_IMAGE,
+                vk::ShaderStageFlags::COMPUTE
+            )]
+        );
+        assert_eq!(
+            push_constants(&interface),
+            [(0, 4, vk::ShaderStageFlags::COMPUTE)]
+        );
+        assert_eq!(interface.local_size, [8, 8, 1]);
+    }
+
+    #[test]
+    fn load_spirv_rejects_bad_input() {
+        let bytes = shaders::VERTEX;
+        assert!(load_spirv(bytes).is_ok());
+        assert!(matches!(
+            load_spirv(&bytes[..bytes.len() - 1]),
+            Err(ReflectError::InvalidSpirv(_))
+        ));
+        assert!(matches!(
+            load_spirv(&bytes[..16]),
+            Err(ReflectError::InvalidSpirv(_))
+        ));
+        assert!(matches!(
+            load_spirv(&[]),
+            Err(ReflectError::InvalidSpirv(_))
+        ));
+
+        let mut bad_magic = bytes.to_vec();
+        bad_magic[0] ^= 0xff;
+        assert!(matches!(
+            load_spirv(&bad_magic),
+            Err(ReflectError::InvalidSpirv(_))
+        ));
+    }
+
+    #[test]
+    fn load_spirv_swaps_endianness() {
+        let swapped: Vec<u8> = shaders::VERTEX
+            .chunks_exact(4)
+            .flat_map(|word| [word[3], word[2], word[1], word[0]])
+            .collect();
+        assert_eq!(
+            load_spirv(&swapped).unwrap(),
+            load_spirv(shaders::VERTEX).unwrap()
+        );
+    }
+
+    //  Hand assembled, one instruction per line:
+    //  layout(constant_id = 7) const uint COUNT = 2;
+    //  layout(binding = 3) uniform sampler u_samplers[COUNT];
+    #[rustfmt::skip]
+    fn spec_constant_array_module() -> Vec<u32> {
+        let (main, uint, count, sampler, array, pointer, var) = (1, 2, 3, 4, 5, 6, 7);
+        let main_name = u32::from_le_bytes(*b"main");
+        vec![
+            SPIRV_MAGIC, 0x0001_0000, 0, 8, 0,
+            2 << 16 | 17, 1,
+            3 << 16 | 14, 0, 1,
+            5 << 16 | OP_ENTRY_POINT, 5, main, main_name, 0,
+            6 << 16 | OP_EXECUTION_MODE, main, EXECUTION_MODE_LOCAL_SIZE, 1, 1, 1,
+            4 << 16 | OP_DECORATE, count, DECORATION_SPEC_ID, 7,
+            4 << 16 | OP_DECORATE, var, DECORATION_DESCRIPTOR_SET, 0,
+            4 << 16 | OP_DECORATE, var, DECORATION_BINDING, 3,
+            4 << 16 | OP_TYPE_INT, uint, 32, 0,
+            4 << 16 | OP_SPEC_CONSTANT, uint, count, 2,
+            2 << 16 | OP_TYPE_SAMPLER, sampler,
+            4 << 16 | OP_TYPE_ARRAY, array, sampler, count,
+            4 << 16 | OP_TYPE_POINTER, pointer, STORAGE_UNIFORM_CONSTANT, array,
+            4 << 16 | OP_VARIABLE, pointer, var, STORAGE_UNIFORM_CONSTANT,
+        ]
+    }
+
+    #[test]
+    fn reflect_spec_constant_array_length() {
+        let code = spec_constant_array_module();
+        let interface = ShaderInterface::reflect(&[&code]).unwrap();
+        assert_eq!(
+            bindings(&interface),
+            [(
+                3,
+                vk::DescriptorType::SAMPLER,
+                2,
+                vk::ShaderStageFlags::COMPUTE
+            )]
+        );
+
+        let specialization = Specialization::default().constant(7, 5u32);
+        let interface = ShaderInterface::reflect_specialized(&[(&code, &specialization)]).unwrap();
+        assert_eq!(
+            bindings(&interface),
+            [(
+                3,
+                vk::DescriptorType::SAMPLER,
+                5,
+                vk::ShaderStageFlags::COMPUTE
+            )]
+        );
+    }
+
+    #[test]
+    fn reflect_rejects_truncated_instructions() {
+        let mut code = load_spirv(shaders::VERTEX).unwrap();
+        //  Claim that the first instruction after the header runs past the end.
+        code[5] |= 0xffff << 16;
+        assert!(matches!(
+            ShaderInterface::reflect(&[&code]),
+            Err(ReflectError::InvalidSpirv(_))
+        ));
+    }
+}
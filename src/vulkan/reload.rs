@@ -0,0 +1,160 @@
+use super::PERMUTATIONS;
+use shaderc::*;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+struct WatchedShader {
+    name: String,
+    path: PathBuf,
+    kind: ShaderKind,
+    modified: Option<SystemTime>,
+}
+
+//  Compiles GLSL at runtime and notices when the sources change.
+//  There's no file notification crate here, so we just poll modification times every frame.
+pub struct ShaderReloader {
+    compiler: Compiler,
+    shaders: Vec<WatchedShader>,
+    //  Same as `INCLUDE_DIRS` in build.rs.
+    include_dir: PathBuf,
+    includes: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ShaderReloader {
+    pub fn create(dir: &str) -> Option<Self> {
+        let compiler = Compiler::new()?;
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("[Reload] Could not read {}: {}", dir, e);
+                None?
+            }
+        };
+        let shaders = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let kind = match path.extension()?.to_str()? {
+                    "vert" => ShaderKind::Vertex,
+                    "frag" => ShaderKind::Fragment,
+                    "comp" => ShaderKind::Compute,
+                    "geom" => ShaderKind::Geometry,
+                    "tesc" => ShaderKind::TessControl,
+                    "tese" => ShaderKind::TessEvaluation,
+                    _ => None?,
+                };
+                Some(WatchedShader {
+                    name: path.file_stem()?.to_str()?.to_owned(),
+                    modified: get_modified(&path),
+                    path,
+                    kind,
+                })
+            })
+            .collect();
+
+        //  Includes may be shared, so changing one counts as changing every shader.
+        let include_dir = Path::new(dir).join("include");
+        let includes = std::fs::read_dir(&include_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                Some((path.clone(), get_modified(&path)))
+            })
+            .collect();
+
+        Some(ShaderReloader {
+            compiler,
+            shaders,
+            include_dir,
+            includes,
+        })
+    }
+
+    //  Returns the names (`src/vertex.vert` -> "vertex") of every shader that changed since the
+    //  last poll.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut includes_changed = false;
+        for (path, modified) in self.includes.iter_mut() {
+            let now = get_modified(path);
+            if now != *modified {
+                *modified = now;
+                includes_changed = true;
+            }
+        }
+        self.shaders
+            .iter_mut()
+            .filter_map(|shader| {
+                let modified = get_modified(&shader.path);
+                (includes_changed || modified != shader.modified).then(|| {
+                    shader.modified = modified;
+                    shader.name.clone()
+                })
+            })
+            .collect()
+    }
+
+    //  Compiler errors are printed rather than panicking so that a typo doesn't kill the app.
+    //  `name` can also be the output of one of `PERMUTATIONS`.
+    pub fn compile(&self, name: &str) -> Option<Vec<u32>> {
+        let (input, defines) = PERMUTATIONS
+            .iter()
+            .find(|(_, output, _)| *output == name)
+            .map_or((name, &[][..]), |(input, _, defines)| (*input, *defines));
+        let shader = self.shaders.iter().find(|shader| shader.name == input)?;
+        let input_file = shader.path.to_string_lossy();
+        let source = match std::fs::read_to_string(&shader.path) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("[Reload] Could not read {}: {}", input_file, e);
+                None?
+            }
+        };
+        let mut options = CompileOptions::new()?;
+        defines
+            .iter()
+            .for_each(|(name, value)| options.add_macro_definition(name, *value));
+        options.set_include_callback(|requested, ty, requesting, _depth| {
+            let relative = Path::new(requesting)
+                .parent()
+                .filter(|_| ty == IncludeType::Relative)
+                .map(|dir| dir.join(requested));
+            let path = relative
+                .into_iter()
+                .chain(std::iter::once(self.include_dir.join(requested)))
+                .find(|path| path.is_file())
+                .ok_or_else(|| {
+                    format!("Could not find {} (included by {})", requested, requesting)
+                })?;
+            Ok(ResolvedInclude {
+                resolved_name: path.to_string_lossy().into_owned(),
+                content: std::fs::read_to_string(&path).map_err(|e| e.to_string())?,
+            })
+        });
+        match self.compiler.compile_into_spirv(
+            &source,
+            shader.kind,
+            &input_file,
+            "main",
+            Some(&options),
+        ) {
+            Ok(binary) => {
+                if binary.get_num_warnings() > 0 {
+                    println!("[Reload] {}", binary.get_warning_messages());
+                }
+                Some(binary.as_binary().to_vec())
+            }
+            Err(e) => {
+                println!("[Reload] Failed to compile {}:\n{}", input_file, e);
+                None
+            }
+        }
+    }
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
@@ -1,143 +1,169 @@
 use super::*;
 
-pub struct VulkanRender {
-    pub depth_image: Image,
-    pub depth_image_view: vk::ImageView,
+//  Best first. The ones with a stencil come first so that outlines and masking work.
+pub const DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+    vk::Format::D32_SFLOAT,
+];
+
+pub fn has_stencil(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D32_SFLOAT_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::S8_UINT
+    )
+}
+
+pub fn is_depth(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::X8_D24_UNORM_PACK32
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+//  What views of, and barriers on, images of `format` have to cover
+pub fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    if is_depth(format) {
+        depth_aspect(format)
+    } else {
+        vk::ImageAspectFlags::COLOR
+    }
+}
+
+//  Attachment views of combined formats have to cover both aspects.
+pub fn depth_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    if has_stencil(format) {
+        vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+    } else {
+        vk::ImageAspectFlags::DEPTH
+    }
+}
+
+//  What happens to the stencil around the render pass.
+//  Ignored when the depth format has no stencil.
+#[derive(Clone, Copy)]
+pub struct StencilOps {
+    pub load: vk::AttachmentLoadOp,
+    pub store: vk::AttachmentStoreOp,
+}
+
+impl Default for StencilOps {
+    //  Cleared every frame and forgotten afterwards
+    fn default() -> Self {
+        StencilOps {
+            load: vk::AttachmentLoadOp::CLEAR,
+            store: vk::AttachmentStoreOp::DONT_CARE,
+        }
+    }
+}
+
+//  Everything a pipeline has to know about the pass it draws in.
+//  With dynamic rendering there is no render pass, pipelines are built against `layout` instead.
+#[derive(Clone)]
+pub struct PassInfo {
     pub render_pass: vk::RenderPass,
-    pub framebuffers: Vec<vk::Framebuffer>,
+    pub layout: PassLayout,
+    pub extent: vk::Extent2D,
+    pub subpass: u32,
 }
 
-impl VulkanRender {
-    pub fn create(bvk: &BabyVulkan, swappy: &VulkanSwapchain) -> Option<Self> {
-        //  Create Depth Image
-        let depth_image_format = vk::Format::D32_SFLOAT;
-        let depth_image = Image::create(
-            bvk,
-            depth_image_format,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-            vk::Extent3D {
-                width: swappy.extent.width,
-                height: swappy.extent.height,
-                depth: 1,
-            },
-        )?;
-        let depth_image_view = bvk.create_image_view(
-            depth_image.image,
-            depth_image_format,
-            vk::ImageAspectFlags::DEPTH,
-        )?;
+//  The scene is drawn with more range than the swapchain has, `PostChain` tonemaps it down.
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
 
-        //  Create Color Attachment
-        let color_attachment = vk::AttachmentDescription::builder()
-            .format(swappy.format)
-            //  No MSAA
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            //  No stencil, so we don't care
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            //  We're clearing anyway, so this can be undefined
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .build();
-        let color_attachment_ref = vk::AttachmentReference::builder()
-            //  Index into the attachments of the render pass itself
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build();
-
-        //  Create Depth Attachment
-        let depth_attachment = vk::AttachmentDescription::builder()
-            .format(depth_image_format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-        let depth_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(1)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-
-        //  Create Render Pass Subpass
-        let subpass = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&[color_attachment_ref])
-            .depth_stencil_attachment(&depth_attachment_ref)
-            .build();
-
-        let color_dependency = vk::SubpassDependency::builder()
-            //  Finish running `vk::SUBPASS_EXTERNAL` (the subpass of the previous frame) before
-            //  running subpass # 0
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            //  Make sure you finish running `src_stage_mask`
-            //  before running `dst_stage_mask`
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-            .build();
-
-        let depth_dependency = vk::SubpassDependency::builder()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
-                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
-            )
-            .dst_stage_mask(
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
-                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
-            )
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
-            .build();
-
-        //  Create Render Pass
-        let render_pass_info = vk::RenderPassCreateInfo::builder()
-            .dependencies(&[color_dependency, depth_dependency])
-            .attachments(&[color_attachment, depth_attachment])
-            .subpasses(&[subpass])
-            .build();
-        let render_pass = unsafe { bvk.dev.create_render_pass(&render_pass_info, None) }.ok()?;
-
-        //  Create Framebuffers
-        let framebuffers: Vec<vk::Framebuffer> = swappy
-            .swapchain_image_views
-            .iter()
-            .map(|view| {
-                let framebuffer_info = vk::FramebufferCreateInfo::builder()
-                    .render_pass(render_pass)
-                    .attachments(&[*view, depth_image_view])
-                    .width(swappy.extent.width)
-                    .height(swappy.extent.height)
-                    .layers(1)
-                    .build();
-                unsafe { bvk.dev.create_framebuffer(&framebuffer_info, None).ok() }
-            })
-            .collect::<Option<_>>()?;
+pub const DEPTH_CLEAR_VALUE: vk::ClearValue = vk::ClearValue {
+    depth_stencil: vk::ClearDepthStencilValue {
+        depth: 1.0,
+        stencil: 0,
+    },
+};
+
+//  The main pass. It draws the scene in HDR, for the post chain to pick up.
+//  The images come from the render graph, this only knows what they look like.
+pub struct VulkanRender {
+    pub extent: vk::Extent2D,
+    pub samples: vk::SampleCountFlags,
+    pub depth_format: vk::Format,
+    pub stencil_ops: StencilOps,
+}
 
+impl VulkanRender {
+    pub fn create(
+        bvk: &BabyVulkan,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+        stencil_ops: StencilOps,
+    ) -> Option<Self> {
+        let depth_format = bvk.find_format(
+            &DEPTH_FORMATS,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )?;
         Some(VulkanRender {
-            depth_image,
-            depth_image_view,
-            render_pass,
-            framebuffers,
+            extent,
+            samples,
+            depth_format,
+            stencil_ops,
         })
     }
 
-    pub fn destroy(&mut self, bvk: &BabyVulkan) {
-        unsafe {
-            bvk.dev.destroy_image_view(self.depth_image_view, None);
-            self.depth_image.destroy(bvk);
-            self.framebuffers
-                .iter()
-                .for_each(|framebuffer| bvk.dev.destroy_framebuffer(*framebuffer, None));
-            bvk.dev.destroy_render_pass(self.render_pass, None);
+    pub fn has_stencil(&self) -> bool {
+        has_stencil(self.depth_format)
+    }
+
+    pub fn layout(&self) -> PassLayout {
+        PassLayout {
+            colors: vec![HDR_FORMAT],
+            depth: Some(self.depth_format),
+            samples: self.samples,
+            resolve: self.samples != vk::SampleCountFlags::TYPE_1,
         }
     }
+
+    //  Adds the main pass and its images to `graph`.
+    //  Returns the pass, so that whatever the scene reads can be added to it, and the scene itself.
+    pub fn add_pass<'a>(
+        &self,
+        graph: &'a mut RenderGraph,
+        clear_color: [f32; 4],
+    ) -> (PassBuilder<'a>, ImageHandle) {
+        let color = graph.create_image(
+            "scene",
+            ImageDesc::color(HDR_FORMAT, self.extent, vk::SampleCountFlags::TYPE_1),
+        );
+        //  With MSAA, the samples are thrown away once they're resolved into `color`.
+        let msaa_color = (self.samples != vk::SampleCountFlags::TYPE_1).then(|| {
+            graph.create_image(
+                "scene_msaa",
+                ImageDesc::color(HDR_FORMAT, self.extent, self.samples),
+            )
+        });
+        let depth = graph.create_image(
+            "scene_depth",
+            ImageDesc::depth(self.depth_format, self.extent, self.samples),
+        );
+
+        let mut pass = graph.add_pass("scene", PassKind::Graphics);
+        let clear = LoadOp::Clear(vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: clear_color,
+            },
+        });
+        let scene = match msaa_color {
+            Some(msaa_color) => {
+                pass.color(msaa_color, clear);
+                pass.resolve(color)
+            }
+            None => pass.color(color, clear),
+        };
+        pass.depth(depth, LoadOp::Clear(DEPTH_CLEAR_VALUE));
+        pass.stencil_ops(self.stencil_ops);
+        (pass, scene)
+    }
 }
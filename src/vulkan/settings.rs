@@ -0,0 +1,202 @@
+use super::*;
+
+//  Everything that can be picked on the command line, e.g. `cargo run -- --samples 8`.
+//  Some of it can also be changed while running, see `VulkanPlayground::key_pressed`.
+pub struct Settings {
+    //  MSAA samples per pixel, clamped to what the device supports.
+    pub samples: u32,
+    //  Which post effects to run and in what order, e.g. `--post tonemap,fxaa`.
+    //  `None` runs the default chain.
+    pub post: Option<Vec<String>>,
+    //  Use dynamic rendering if the device has it, `--render-passes` sticks to render passes.
+    pub dynamic_rendering: bool,
+    //  Light the scene from a G-buffer instead of while drawing it, `--deferred`.
+    pub deferred: bool,
+    //  Present in an HDR color space if the surface has one, `--hdr`.
+    pub hdr: bool,
+    //  Present in this format if the surface has it, e.g. `--surface-format B8G8R8A8_UNORM`.
+    //  `None` goes by `VulkanSwapchain`'s preference.
+    pub surface_format: Option<vk::Format>,
+    //  `--present vsync|adaptive|mailbox|uncapped`, falls back to what the surface has.
+    pub present_mode: PresentMode,
+    //  How many frames the CPU may get ahead of the GPU, up to `MAX_FRAMES_IN_FLIGHT`.
+    //  More keeps both busy, fewer has less latency.
+    pub frames_in_flight: usize,
+    //  Wait on the GPU with one timeline semaphore instead of fences, `--timeline`.
+    //  Falls back to fences without Vulkan 1.2.
+    pub timeline: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            samples: 4,
+            post: None,
+            dynamic_rendering: true,
+            deferred: false,
+            hdr: false,
+            surface_format: None,
+            present_mode: PresentMode::Adaptive,
+            frames_in_flight: 2,
+            timeline: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_args() -> Self {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    //  Accepts both `--name value` and `--name=value`.
+    //  Anything we don't understand is reported and skipped.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut settings = Settings::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            match name.as_str() {
+                "--samples" => match value.or_else(|| args.next()).and_then(|v| v.parse().ok()) {
+                    Some(samples) => settings.samples = samples,
+                    None => println!("[Settings] --samples needs a number"),
+                },
+                "--post" => match value.or_else(|| args.next()) {
+                    Some(effects) => {
+                        settings.post = Some(
+                            effects
+                                .split(',')
+                                .filter(|effect| !effect.is_empty())
+                                .map(str::to_owned)
+                                .collect(),
+                        )
+                    }
+                    None => println!("[Settings] --post needs a list of effects"),
+                },
+                "--render-passes" => settings.dynamic_rendering = false,
+                "--deferred" => settings.deferred = true,
+                "--hdr" => settings.hdr = true,
+                "--timeline" => settings.timeline = true,
+                "--surface-format" => match value.or_else(|| args.next()) {
+                    Some(name) => match surface_format_from_name(&name) {
+                        Some(format) => settings.surface_format = Some(format),
+                        None => println!("[Settings] Unknown surface format {}", name),
+                    },
+                    None => println!("[Settings] --surface-format needs a format"),
+                },
+                "--frames-in-flight" => {
+                    match value.or_else(|| args.next()).and_then(|v| v.parse().ok()) {
+                        Some(count) if (1..=MAX_FRAMES_IN_FLIGHT).contains(&count) => {
+                            settings.frames_in_flight = count
+                        }
+                        _ => println!(
+                            "[Settings] --frames-in-flight needs a number from 1 to {}",
+                            MAX_FRAMES_IN_FLIGHT
+                        ),
+                    }
+                }
+                "--present" => match value
+                    .or_else(|| args.next())
+                    .and_then(|name| PresentMode::from_name(&name))
+                {
+                    Some(mode) => settings.present_mode = mode,
+                    None => {
+                        println!("[Settings] --present needs vsync, adaptive, mailbox or uncapped")
+                    }
+                },
+                _ => println!("[Settings] Unknown argument {}", name),
+            }
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Settings {
+        Settings::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_samples() {
+        assert_eq!(parse(&[]).samples, 4);
+        assert_eq!(parse(&["--samples", "8"]).samples, 8);
+        assert_eq!(parse(&["--samples=2"]).samples, 2);
+        //  Bad values keep the default
+        assert_eq!(parse(&["--samples", "lots"]).samples, 4);
+        assert_eq!(parse(&["--bogus", "--samples=1"]).samples, 1);
+    }
+
+    #[test]
+    fn parse_post() {
+        assert_eq!(parse(&[]).post, None);
+        assert_eq!(
+            parse(&["--post", "fxaa,tonemap"]).post,
+            Some(vec!["fxaa".to_owned(), "tonemap".to_owned()])
+        );
+        //  Nothing at all is allowed, that just copies the scene to the screen
+        assert_eq!(parse(&["--post="]).post, Some(vec![]));
+    }
+
+    #[test]
+    fn parse_render_passes() {
+        assert!(parse(&[]).dynamic_rendering);
+        assert!(!parse(&["--render-passes"]).dynamic_rendering);
+        assert_eq!(parse(&["--render-passes", "--samples", "2"]).samples, 2);
+    }
+
+    #[test]
+    fn parse_deferred() {
+        assert!(!parse(&[]).deferred);
+        assert!(parse(&["--deferred"]).deferred);
+    }
+
+    #[test]
+    fn parse_hdr() {
+        assert!(!parse(&[]).hdr);
+        assert!(parse(&["--hdr", "--deferred"]).hdr);
+    }
+
+    #[test]
+    fn parse_surface_format() {
+        assert_eq!(parse(&[]).surface_format, None);
+        assert_eq!(
+            parse(&["--surface-format", "B8G8R8A8_UNORM"]).surface_format,
+            Some(vk::Format::B8G8R8A8_UNORM)
+        );
+        assert_eq!(parse(&["--surface-format=bogus"]).surface_format, None);
+    }
+
+    #[test]
+    fn parse_present_mode() {
+        assert_eq!(parse(&[]).present_mode, PresentMode::Adaptive);
+        assert_eq!(
+            parse(&["--present", "uncapped"]).present_mode,
+            PresentMode::Uncapped
+        );
+        assert_eq!(parse(&["--present=Vsync"]).present_mode, PresentMode::Vsync);
+        assert_eq!(
+            parse(&["--present", "fast"]).present_mode,
+            PresentMode::Adaptive
+        );
+    }
+
+    #[test]
+    fn parse_frames_in_flight() {
+        assert_eq!(parse(&[]).frames_in_flight, 2);
+        assert_eq!(parse(&["--frames-in-flight", "3"]).frames_in_flight, 3);
+        //  Out of range keeps the default
+        assert_eq!(parse(&["--frames-in-flight=0"]).frames_in_flight, 2);
+        assert_eq!(parse(&["--frames-in-flight", "9"]).frames_in_flight, 2);
+    }
+
+    #[test]
+    fn parse_timeline() {
+        assert!(!parse(&[]).timeline);
+        assert!(parse(&["--timeline"]).timeline);
+    }
+}
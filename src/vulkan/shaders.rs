@@ -0,0 +1,5 @@
+//  The SPIR-V of every shader in `src/`, compiled by build.rs.
+//  Not every shader is used all of the time.
+#![allow(dead_code)]
+
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
@@ -0,0 +1,105 @@
+use super::*;
+
+pub const SHADOW_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 2048,
+    height: 2048,
+};
+
+//  Best first. None of them have a stencil, so that views of them can be sampled.
+pub const SHADOW_FORMATS: [vk::Format; 2] = [vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+
+//  How far around the origin the light looks. Anything further out is never in shadow.
+const SHADOW_RADIUS: f32 = 3.0;
+
+//  Depth as seen from a directional light, drawn every frame before anything that is lit.
+//  The scene compares against it to find out what the light can't see.
+pub struct ShadowMap {
+    pub texture: Texture,
+    pub pipeline: VulkanPipeline,
+    //  Towards the light, in world space
+    pub light_dir: glm::Vec3,
+}
+
+impl ShadowMap {
+    pub fn create(bvk: &BabyVulkan, cache: &mut GraphCache, light_dir: glm::Vec3) -> Option<Self> {
+        let format = bvk.find_format(
+            &SHADOW_FORMATS,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )?;
+        let texture = Texture::create_shadow_map(bvk, format, SHADOW_EXTENT)?;
+        let pipeline = Self::create_pipeline(
+            bvk,
+            cache,
+            format,
+            &VulkanPipeline::read_shader_code(shaders::SHADOW)?,
+            &VulkanPipeline::read_shader_code(shaders::DEPTH_ONLY)?,
+        )?;
+        Some(ShadowMap {
+            texture,
+            pipeline,
+            light_dir: glm::normalize(&light_dir),
+        })
+    }
+
+    //  Only the vertex positions are used, but any `Vertex` buffer will do.
+    pub fn create_pipeline(
+        bvk: &BabyVulkan,
+        cache: &mut GraphCache,
+        format: vk::Format,
+        vert_code: &[u32],
+        frag_code: &[u32],
+    ) -> Option<VulkanPipeline> {
+        let layout = PassLayout {
+            colors: vec![],
+            depth: Some(format),
+            samples: vk::SampleCountFlags::TYPE_1,
+            resolve: false,
+        };
+        let pass = cache.pass_info(bvk, &layout, SHADOW_EXTENT)?;
+        VulkanPipeline::create(
+            bvk,
+            &pass,
+            vert_code,
+            frag_code,
+            &PipelineVariant::default(),
+            &[Vertex::bindings()],
+            &Vertex::attributes(),
+            &[],
+        )
+    }
+
+    //  World space to the light's clip space.
+    //  Orthographic, since a directional light is infinitely far away.
+    pub fn light_view_proj(&self) -> glm::Mat4 {
+        let eye = self.light_dir * SHADOW_RADIUS * 2.0;
+        let view = glm::look_at(&eye, &glm::vec3(0.0, 0.0, 0.0), &glm::vec3(0.0, 1.0, 0.0));
+        let proj = glm::ortho_rh_zo(
+            -SHADOW_RADIUS,
+            SHADOW_RADIUS,
+            -SHADOW_RADIUS,
+            SHADOW_RADIUS,
+            0.1,
+            SHADOW_RADIUS * 4.0,
+        );
+        proj * view
+    }
+
+    //  Adds the shadow pass and the shadow map to `graph`.
+    //  Returns the pass, so that the vertex buffers can be added to it, and the finished map.
+    pub fn add_pass<'a>(&self, graph: &'a mut RenderGraph) -> (PassBuilder<'a>, ImageHandle) {
+        let shadow_map = graph.import_image(
+            "shadow_map",
+            ImportedImage::texture(&self.texture, SHADOW_EXTENT),
+        );
+        let mut pass = graph.add_pass("shadow", PassKind::Graphics);
+        let shadow_map = pass.depth(shadow_map, LoadOp::Clear(DEPTH_CLEAR_VALUE));
+        (pass, shadow_map)
+    }
+
+    pub fn destroy(&mut self, bvk: &BabyVulkan) {
+        self.pipeline.destroy(bvk);
+        self.texture.destroy(bvk);
+    }
+}
@@ -0,0 +1,131 @@
+use super::*;
+
+//  Anything that can be a `layout(constant_id = N) const` in GLSL.
+//  Every one of them happens to be 4 bytes.
+pub trait SpecializationConstant {
+    fn to_bytes(&self) -> [u8; 4];
+}
+
+impl SpecializationConstant for bool {
+    //  `bool` constants are `VkBool32`s on our side.
+    fn to_bytes(&self) -> [u8; 4] {
+        (if *self { vk::TRUE } else { vk::FALSE }).to_ne_bytes()
+    }
+}
+
+impl SpecializationConstant for u32 {
+    fn to_bytes(&self) -> [u8; 4] {
+        self.to_ne_bytes()
+    }
+}
+
+impl SpecializationConstant for i32 {
+    fn to_bytes(&self) -> [u8; 4] {
+        self.to_ne_bytes()
+    }
+}
+
+impl SpecializationConstant for f32 {
+    fn to_bytes(&self) -> [u8; 4] {
+        self.to_ne_bytes()
+    }
+}
+
+//  The specialization constants of a single shader stage.
+//  Constants that aren't given keep the default from the shader source.
+//  They are kept sorted by id so that the order they were set in doesn't matter for `Hash`/`Eq`.
+//
+//  Specialization::default()
+//      .constant(0, true)
+//      .constant(1, 4u32)
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Specialization {
+    constants: Vec<(u32, [u8; 4])>,
+}
+
+impl Specialization {
+    pub fn constant<T: SpecializationConstant>(mut self, id: u32, value: T) -> Self {
+        let bytes = value.to_bytes();
+        match self
+            .constants
+            .binary_search_by_key(&id, |(constant_id, _)| *constant_id)
+        {
+            Ok(idx) => self.constants[idx].1 = bytes,
+            Err(idx) => self.constants.insert(idx, (id, bytes)),
+        }
+        self
+    }
+
+    //  The raw 32 bits of constant `id`, if it was given.
+    pub fn get(&self, id: u32) -> Option<u32> {
+        self.constants
+            .binary_search_by_key(&id, |(constant_id, _)| *constant_id)
+            .ok()
+            .map(|idx| u32::from_ne_bytes(self.constants[idx].1))
+    }
+
+    //  `vk::SpecializationInfo` only borrows these, so the caller has to keep them alive.
+    pub fn map_entries_and_data(&self) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+        let map_entries = self
+            .constants
+            .iter()
+            .enumerate()
+            .map(|(idx, (id, _))| {
+                vk::SpecializationMapEntry::builder()
+                    .constant_id(*id)
+                    .offset((idx * 4) as u32)
+                    .size(4)
+                    .build()
+            })
+            .collect();
+        let data = self
+            .constants
+            .iter()
+            .flat_map(|(_, bytes)| *bytes)
+            .collect();
+        (map_entries, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(specialization: &Specialization) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        specialization.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn order_does_not_matter() {
+        let a = Specialization::default()
+            .constant(0, true)
+            .constant(1, 4u32);
+        let b = Specialization::default()
+            .constant(1, 4u32)
+            .constant(0, true);
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+
+        let (map_entries, data) = b.map_entries_and_data();
+        let ids: Vec<(u32, u32)> = map_entries
+            .iter()
+            .map(|entry| (entry.constant_id, entry.offset))
+            .collect();
+        assert_eq!(ids, [(0, 0), (1, 4)]);
+        assert_eq!(data.len(), 8);
+    }
+
+    #[test]
+    fn later_values_win() {
+        let specialization = Specialization::default()
+            .constant(3, 1.0f32)
+            .constant(3, 7i32);
+        assert_eq!(specialization.get(3), Some(7));
+        assert_eq!(specialization.get(4), None);
+        assert_eq!(specialization.map_entries_and_data().0.len(), 1);
+    }
+}
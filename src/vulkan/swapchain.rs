@@ -1,53 +1,205 @@
 use super::*;
 
+//  Whether writes to `format` get encoded to sRGB by the hardware.
+pub fn is_srgb(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+    )
+}
+
+//  Whether presenting in `color_space` needs HDR output, see `post_hdr.frag`.
+pub fn is_hdr(color_space: vk::ColorSpaceKHR) -> bool {
+    matches!(
+        color_space,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT | vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
+    )
+}
+
+//  Surface formats we'd like, best first.
+//  sRGB comes before UNORM so that the hardware does the encoding, and the texture (which is
+//  uploaded as sRGB) looks the same on every machine. `post_gamma.frag` makes up for UNORM.
+const SDR_FORMATS: [(vk::Format, vk::ColorSpaceKHR); 4] = [
+    (vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (
+        vk::Format::B8G8R8A8_UNORM,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+    (
+        vk::Format::R8G8B8A8_UNORM,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+];
+//  Only with `--hdr`, before any of `SDR_FORMATS`
+const HDR_FORMATS: [(vk::Format, vk::ColorSpaceKHR); 3] = [
+    (
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+    (
+        vk::Format::A2R10G10B10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+    (
+        vk::Format::R16G16B16A16_SFLOAT,
+        vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+    ),
+];
+
+//  For `--surface-format`, the formats above by their Vulkan names.
+pub fn surface_format_from_name(name: &str) -> Option<vk::Format> {
+    SDR_FORMATS
+        .iter()
+        .chain(&HDR_FORMATS)
+        .map(|&(format, _)| format)
+        .find(|format| format!("{:?}", format).eq_ignore_ascii_case(name))
+}
+
+//  `wanted` wins if the surface has it, in whatever color space comes first.
+//  Then the best of `HDR_FORMATS` (with `hdr`) and `SDR_FORMATS`, or just whatever comes first.
+pub fn choose_surface_format(
+    formats: &[vk::SurfaceFormatKHR],
+    hdr: bool,
+    wanted: Option<vk::Format>,
+) -> Option<vk::SurfaceFormatKHR> {
+    let hdr_formats: &[(vk::Format, vk::ColorSpaceKHR)] = if hdr { &HDR_FORMATS } else { &[] };
+    let ranked = hdr_formats
+        .iter()
+        .chain(&SDR_FORMATS)
+        .find_map(|&(format, color_space)| {
+            formats
+                .iter()
+                .find(|f| f.format == format && f.color_space == color_space)
+        });
+    wanted
+        .and_then(|wanted| formats.iter().find(|f| f.format == wanted))
+        .or(ranked)
+        .or_else(|| formats.first())
+        .copied()
+}
+
+//  How frames get to the screen, `--present` or V while running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    //  Wait for vblank, never tears
+    Vsync,
+    //  Like `Vsync`, but late frames go out right away and may tear
+    Adaptive,
+    //  Wait for vblank, but always show the newest frame
+    Mailbox,
+    //  Don't wait at all
+    Uncapped,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 4] = [
+        PresentMode::Vsync,
+        PresentMode::Adaptive,
+        PresentMode::Mailbox,
+        PresentMode::Uncapped,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| format!("{:?}", mode).eq_ignore_ascii_case(name))
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    //  Best first. Every surface has FIFO, so that is the last resort.
+    fn candidates(self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            PresentMode::Vsync => &[vk::PresentModeKHR::FIFO],
+            PresentMode::Adaptive => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+            PresentMode::Mailbox => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            PresentMode::Uncapped => &[
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO,
+            ],
+        }
+    }
+}
+
+pub fn choose_present_mode(
+    modes: &[vk::PresentModeKHR],
+    wanted: PresentMode,
+) -> vk::PresentModeKHR {
+    wanted
+        .candidates()
+        .iter()
+        .copied()
+        .find(|mode| modes.contains(mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+//  One more than the minimum, so that we aren't left waiting on the driver for an image to draw
+//  into. A `max_image_count` of 0 means there is no maximum.
+pub fn choose_image_count(caps: &vk::SurfaceCapabilitiesKHR) -> u32 {
+    let count = caps.min_image_count + 1;
+    if caps.max_image_count > 0 {
+        count.min(caps.max_image_count)
+    } else {
+        count
+    }
+}
+
 pub struct VulkanSwapchain {
     pub format: vk::Format,
+    pub color_space: vk::ColorSpaceKHR,
+    pub present_mode: vk::PresentModeKHR,
     pub extent: vk::Extent2D,
     pub swapchain_ext: extensions::khr::Swapchain,
     pub swapchain: vk::SwapchainKHR,
+    pub swapchain_images: Vec<vk::Image>,
     pub swapchain_image_views: Vec<vk::ImageView>,
 }
 
 impl VulkanSwapchain {
-    pub fn create(bvk: &BabyVulkan, w: u32, h: u32) -> Option<Self> {
+    //  With `old`, the new swapchain takes over from it, so that the old images can still be
+    //  presented while it's being made. `old` is retired either way, and still has to be destroyed.
+    pub fn create(
+        bvk: &BabyVulkan,
+        w: u32,
+        h: u32,
+        settings: &Settings,
+        old: Option<&VulkanSwapchain>,
+    ) -> Option<Self> {
         let (surface_caps, surface_formats, surface_presents) = bvk.get_surface_data()?;
-
-        //  Choose Swapchain Extent
-        //  Vulkan throws strange errors here.
-        //  Perhaps this is an issue with winit? My driver?
-        //  Maybe a result of Xorg's latency? I don't know.
-        let extent = surface_caps.current_extent;
-        let extent = if extent.width >= surface_caps.min_image_extent.width
-            && extent.width <= surface_caps.max_image_extent.width
-            && extent.height >= surface_caps.min_image_extent.height
-            && extent.height <= surface_caps.max_image_extent.height
-        {
-            extent
-        } else {
-            vk::Extent2D {
-                width: w.clamp(
-                    surface_caps.min_image_extent.width,
-                    surface_caps.max_image_extent.width,
-                ),
-                height: h.clamp(
-                    surface_caps.min_image_extent.height,
-                    surface_caps.max_image_extent.height,
-                ),
-            }
-        };
+        let extent = Self::choose_extent(&surface_caps, w, h);
+        if extent.width == 0 || extent.height == 0 {
+            println!("[Swapchain] The window has no area");
+            None?;
+        }
 
         //  Choose Swapchain Format
-        let format = surface_formats.into_iter().next()?;
+        let format =
+            choose_surface_format(&surface_formats, settings.hdr, settings.surface_format)?;
+        match settings.surface_format {
+            Some(wanted) if wanted != format.format => {
+                println!("[Swapchain] The surface has no {:?}", wanted)
+            }
+            _ if settings.hdr && !is_hdr(format.color_space) => {
+                println!("[Swapchain] No HDR color space")
+            }
+            _ => {}
+        }
+        println!(
+            "[Swapchain] Presenting {:?} in {:?}",
+            format.format, format.color_space
+        );
 
         //  Choose Swapchain Present
-        let present = [
-            vk::PresentModeKHR::FIFO_RELAXED,
-            vk::PresentModeKHR::FIFO,
-            vk::PresentModeKHR::MAILBOX,
-            vk::PresentModeKHR::IMMEDIATE,
-        ]
-        .into_iter()
-        .find(|mode| surface_presents.iter().any(|p| p == mode))?;
+        let present = choose_present_mode(&surface_presents, settings.present_mode);
 
         //  Create the Swapchain
         let swapchain_ext = extensions::khr::Swapchain::new(&bvk.instance, &bvk.dev);
@@ -57,29 +209,79 @@ impl VulkanSwapchain {
             .present_mode(present)
             .image_format(format.format)
             .image_color_space(format.color_space)
-            .min_image_count(surface_caps.min_image_count)
+            .min_image_count(choose_image_count(&surface_caps))
             .pre_transform(surface_caps.current_transform)
             .image_array_layers(1)
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .clipped(true)
+            .old_swapchain(old.map_or(vk::SwapchainKHR::null(), |old| old.swapchain))
             .build();
         let swapchain = unsafe { swapchain_ext.create_swapchain(&swapchain_info, None) }.ok()?;
         let swapchain_images = unsafe { swapchain_ext.get_swapchain_images(swapchain) }.ok()?;
         let swapchain_image_views: Vec<vk::ImageView> = swapchain_images
-            .into_iter()
-            .map(|img| bvk.create_image_view(img, format.format, vk::ImageAspectFlags::COLOR))
+            .iter()
+            .map(|&img| bvk.create_image_view(img, format.format, vk::ImageAspectFlags::COLOR))
             .collect::<Option<_>>()?;
 
         Some(VulkanSwapchain {
             format: format.format,
+            color_space: format.color_space,
+            present_mode: present,
             extent,
             swapchain,
             swapchain_ext,
+            swapchain_images,
             swapchain_image_views,
         })
     }
 
+    //  What the surface wants, or failing that the window's size.
+    //  Zero while the window is minimized, and no swapchain can be made then.
+    pub fn surface_extent(bvk: &BabyVulkan, w: u32, h: u32) -> Option<vk::Extent2D> {
+        let (surface_caps, _, _) = bvk.get_surface_data()?;
+        Some(Self::choose_extent(&surface_caps, w, h))
+    }
+
+    //  Vulkan throws strange errors here.
+    //  Perhaps this is an issue with winit? My driver?
+    //  Maybe a result of Xorg's latency? I don't know.
+    fn choose_extent(surface_caps: &vk::SurfaceCapabilitiesKHR, w: u32, h: u32) -> vk::Extent2D {
+        let extent = surface_caps.current_extent;
+        if extent.width >= surface_caps.min_image_extent.width
+            && extent.width <= surface_caps.max_image_extent.width
+            && extent.height >= surface_caps.min_image_extent.height
+            && extent.height <= surface_caps.max_image_extent.height
+        {
+            extent
+        } else {
+            vk::Extent2D {
+                width: w.clamp(
+                    surface_caps.min_image_extent.width,
+                    surface_caps.max_image_extent.width,
+                ),
+                height: h.clamp(
+                    surface_caps.min_image_extent.height,
+                    surface_caps.max_image_extent.height,
+                ),
+            }
+        }
+    }
+
+    //  For the render graph. Whatever was in the image is gone, and it can only be drawn into
+    //  once the acquire semaphore was waited on, which happens at `COLOR_ATTACHMENT_OUTPUT`.
+    pub fn import(&self, idx: usize) -> ImportedImage {
+        ImportedImage {
+            image: self.swapchain_images[idx],
+            view: self.swapchain_image_views[idx],
+            desc: ImageDesc::color(self.format, self.extent, vk::SampleCountFlags::TYPE_1),
+            initial: Some(SyncState::after(
+                vk::ImageLayout::UNDEFINED,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )),
+        }
+    }
+
     pub fn destroy(&self, bvk: &BabyVulkan) {
         unsafe {
             self.swapchain_image_views
@@ -89,3 +291,138 @@ impl VulkanSwapchain {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        }
+    }
+
+    #[test]
+    fn chooses_hdr_only_when_asked() {
+        let unorm = surface_format(
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        );
+        let srgb = surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+        let scrgb = surface_format(
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+        );
+        let hdr10 = surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        );
+        let formats = [unorm, srgb, scrgb, hdr10];
+
+        assert_eq!(choose_surface_format(&formats, false, None), Some(srgb));
+        assert_eq!(choose_surface_format(&formats, true, None), Some(hdr10));
+        assert_eq!(
+            choose_surface_format(&formats[..3], true, None),
+            Some(scrgb)
+        );
+        //  No HDR to be had, so sRGB it is
+        assert_eq!(choose_surface_format(&formats[..2], true, None), Some(srgb));
+        assert_eq!(choose_surface_format(&[], false, None), None);
+    }
+
+    #[test]
+    fn chooses_by_rank_then_override() {
+        let unorm = surface_format(
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        );
+        let srgb = surface_format(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+        let odd = surface_format(
+            vk::Format::R5G6B5_UNORM_PACK16,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        );
+
+        //  Not just the first one
+        assert_eq!(
+            choose_surface_format(&[unorm, srgb], false, None),
+            Some(srgb)
+        );
+        assert_eq!(
+            choose_surface_format(&[odd, unorm], false, None),
+            Some(unorm)
+        );
+        assert_eq!(choose_surface_format(&[odd], false, None), Some(odd));
+
+        let wanted = Some(vk::Format::B8G8R8A8_UNORM);
+        assert_eq!(
+            choose_surface_format(&[srgb, unorm], false, wanted),
+            Some(unorm)
+        );
+        assert_eq!(choose_surface_format(&[srgb], false, wanted), Some(srgb));
+    }
+
+    #[test]
+    fn present_modes_fall_back() {
+        use vk::PresentModeKHR as Mode;
+        let all = [
+            Mode::FIFO,
+            Mode::FIFO_RELAXED,
+            Mode::MAILBOX,
+            Mode::IMMEDIATE,
+        ];
+        let wanted: Vec<Mode> = PresentMode::ALL
+            .into_iter()
+            .map(|mode| choose_present_mode(&all, mode))
+            .collect();
+        assert_eq!(
+            wanted,
+            [
+                Mode::FIFO,
+                Mode::FIFO_RELAXED,
+                Mode::MAILBOX,
+                Mode::IMMEDIATE
+            ]
+        );
+
+        assert_eq!(
+            choose_present_mode(&[Mode::FIFO, Mode::MAILBOX], PresentMode::Uncapped),
+            Mode::MAILBOX
+        );
+        assert_eq!(
+            choose_present_mode(&[Mode::FIFO], PresentMode::Adaptive),
+            Mode::FIFO
+        );
+        assert_eq!(PresentMode::Uncapped.next(), PresentMode::Vsync);
+        assert_eq!(
+            PresentMode::from_name("mailbox"),
+            Some(PresentMode::Mailbox)
+        );
+    }
+
+    #[test]
+    fn one_more_image_than_needed() {
+        let caps = |min_image_count, max_image_count| vk::SurfaceCapabilitiesKHR {
+            min_image_count,
+            max_image_count,
+            ..Default::default()
+        };
+        assert_eq!(choose_image_count(&caps(2, 8)), 3);
+        assert_eq!(choose_image_count(&caps(3, 3)), 3);
+        //  No maximum
+        assert_eq!(choose_image_count(&caps(2, 0)), 3);
+    }
+
+    #[test]
+    fn surface_formats_by_name() {
+        assert_eq!(
+            surface_format_from_name("b8g8r8a8_unorm"),
+            Some(vk::Format::B8G8R8A8_UNORM)
+        );
+        assert_eq!(
+            surface_format_from_name("R16G16B16A16_SFLOAT"),
+            Some(vk::Format::R16G16B16A16_SFLOAT)
+        );
+        assert_eq!(surface_format_from_name("D32_SFLOAT"), None);
+    }
+}
@@ -0,0 +1,316 @@
+use super::*;
+
+//  How a pass uses an image or a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub stages: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    //  Ignored for buffers
+    pub layout: vk::ImageLayout,
+    pub writes: bool,
+    //  The old contents don't matter, so a layout transition can start from `UNDEFINED`.
+    pub discard: bool,
+}
+
+impl Access {
+    pub fn read(
+        stages: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        Access {
+            stages,
+            access,
+            layout,
+            writes: false,
+            discard: false,
+        }
+    }
+
+    pub fn write(
+        stages: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+        layout: vk::ImageLayout,
+        discard: bool,
+    ) -> Self {
+        Access {
+            stages,
+            access,
+            layout,
+            writes: true,
+            discard,
+        }
+    }
+}
+
+//  What the next access of an image or buffer has to wait for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncState {
+    pub layout: vk::ImageLayout,
+    //  The last write (or layout transition)
+    pub write_stages: vk::PipelineStageFlags,
+    pub write_access: vk::AccessFlags,
+    //  Stages that already waited for that write
+    pub visible_stages: vk::PipelineStageFlags,
+    //  Everything that read since, the next write has to wait for these too
+    pub read_stages: vk::PipelineStageFlags,
+}
+
+impl SyncState {
+    //  Nothing to wait for, e.g. an image that was just created
+    pub fn new(layout: vk::ImageLayout) -> Self {
+        Self::after(layout, vk::PipelineStageFlags::empty())
+    }
+
+    //  Only usable once `stages` got to it, e.g. a swapchain image after its acquire semaphore
+    pub fn after(layout: vk::ImageLayout, stages: vk::PipelineStageFlags) -> Self {
+        SyncState {
+            layout,
+            write_stages: stages,
+            write_access: vk::AccessFlags::empty(),
+            visible_stages: vk::PipelineStageFlags::empty(),
+            read_stages: vk::PipelineStageFlags::empty(),
+        }
+    }
+
+    //  Move on to `next`, returning the barrier that has to go in between, if any.
+    pub fn access(&mut self, next: Access) -> Option<Barrier> {
+        let transition = self.layout != next.layout;
+        if !next.writes && !transition {
+            //  Reads only wait for the last write, and only once per stage
+            self.read_stages |= next.stages;
+            if self.write_stages.is_empty() || self.visible_stages.contains(next.stages) {
+                return None;
+            }
+            self.visible_stages |= next.stages;
+            return Some(Barrier {
+                src_stages: self.write_stages,
+                src_access: self.write_access,
+                dst_stages: next.stages,
+                dst_access: next.access,
+                old_layout: self.layout,
+                new_layout: self.layout,
+            });
+        }
+
+        //  Writes (and layout transitions, which are writes too) wait for everything before them
+        let src_stages = self.write_stages | self.read_stages;
+        let barrier = if src_stages.is_empty() && !transition {
+            None
+        } else {
+            Some(Barrier {
+                src_stages,
+                src_access: self.write_access,
+                dst_stages: next.stages,
+                dst_access: next.access,
+                old_layout: if next.discard {
+                    vk::ImageLayout::UNDEFINED
+                } else {
+                    self.layout
+                },
+                new_layout: next.layout,
+            })
+        };
+        *self = if next.writes {
+            SyncState {
+                layout: next.layout,
+                write_stages: next.stages,
+                write_access: next.access,
+                visible_stages: vk::PipelineStageFlags::empty(),
+                read_stages: vk::PipelineStageFlags::empty(),
+            }
+        } else {
+            //  Later reads in other stages still have to wait for the transition
+            SyncState {
+                layout: next.layout,
+                write_stages: next.stages,
+                write_access: vk::AccessFlags::empty(),
+                visible_stages: next.stages,
+                read_stages: next.stages,
+            }
+        };
+        barrier
+    }
+}
+
+//  One image or buffer barrier. The layouts are ignored for buffers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrier {
+    pub src_stages: vk::PipelineStageFlags,
+    pub src_access: vk::AccessFlags,
+    pub dst_stages: vk::PipelineStageFlags,
+    pub dst_access: vk::AccessFlags,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+}
+
+//  Barriers that are recorded together, before a pass.
+#[derive(Default)]
+pub struct Barriers {
+    src_stages: vk::PipelineStageFlags,
+    dst_stages: vk::PipelineStageFlags,
+    images: Vec<vk::ImageMemoryBarrier>,
+    buffers: Vec<vk::BufferMemoryBarrier>,
+}
+
+impl Barriers {
+    pub fn image(&mut self, image: vk::Image, aspect: vk::ImageAspectFlags, barrier: Barrier) {
+        self.src_stages |= barrier.src_stages;
+        self.dst_stages |= barrier.dst_stages;
+        self.images.push(
+            vk::ImageMemoryBarrier::builder()
+                .image(image)
+                .old_layout(barrier.old_layout)
+                .new_layout(barrier.new_layout)
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(aspect)
+                        .base_mip_level(0)
+                        .level_count(1)
+                        .base_array_layer(0)
+                        .layer_count(1)
+                        .build(),
+                )
+                .src_access_mask(barrier.src_access)
+                .dst_access_mask(barrier.dst_access)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .build(),
+        );
+    }
+
+    pub fn buffer(&mut self, buffer: vk::Buffer, barrier: Barrier) {
+        self.src_stages |= barrier.src_stages;
+        self.dst_stages |= barrier.dst_stages;
+        self.buffers.push(
+            vk::BufferMemoryBarrier::builder()
+                .buffer(buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .src_access_mask(barrier.src_access)
+                .dst_access_mask(barrier.dst_access)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .build(),
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty() && self.buffers.is_empty()
+    }
+
+    //  Everything in one go. That waits a bit more than needed, but there are only a handful.
+    pub fn record(&self, bvk: &BabyVulkan, cmd_buf: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        let src_stages = if self.src_stages.is_empty() {
+            vk::PipelineStageFlags::TOP_OF_PIPE
+        } else {
+            self.src_stages
+        };
+        let dst_stages = if self.dst_stages.is_empty() {
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE
+        } else {
+            self.dst_stages
+        };
+        unsafe {
+            bvk.dev.cmd_pipeline_barrier(
+                cmd_buf,
+                src_stages,
+                dst_stages,
+                vk::DependencyFlags::empty(),
+                &[],
+                &self.buffers,
+                &self.images,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR_WRITE: Access = Access {
+        stages: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        access: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        writes: true,
+        discard: true,
+    };
+    const SAMPLE: Access = Access {
+        stages: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        access: vk::AccessFlags::SHADER_READ,
+        layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        writes: false,
+        discard: false,
+    };
+
+    #[test]
+    fn draw_then_sample() {
+        let mut state = SyncState::new(vk::ImageLayout::UNDEFINED);
+        let first = state.access(COLOR_WRITE).unwrap();
+        assert_eq!(first.old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(first.new_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let sample = state.access(SAMPLE).unwrap();
+        assert_eq!(
+            sample.src_stages,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(sample.src_access, vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        assert_eq!(sample.new_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        //  Sampling again needs nothing more
+        assert_eq!(state.access(SAMPLE), None);
+
+        //  Drawing over it again has to wait for the sampling, but not for its writes
+        let again = state.access(COLOR_WRITE).unwrap();
+        assert_eq!(again.src_stages, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        assert_eq!(again.src_access, vk::AccessFlags::empty());
+        assert_eq!(again.old_layout, vk::ImageLayout::UNDEFINED);
+    }
+
+    #[test]
+    fn buffer_reads_wait_once_per_stage() {
+        let layout = vk::ImageLayout::UNDEFINED;
+        let compute_write = Access::write(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            layout,
+            false,
+        );
+        let vertex_read = Access::read(
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            layout,
+        );
+
+        //  Nothing happened to a new buffer yet
+        let mut state = SyncState::new(layout);
+        assert_eq!(state.access(compute_write), None);
+        assert!(state.access(vertex_read).is_some());
+        assert_eq!(state.access(vertex_read), None);
+
+        //  The next frame's write waits for the draws, and for the write before it
+        let next = state.access(compute_write).unwrap();
+        assert_eq!(
+            next.src_stages,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::VERTEX_INPUT
+        );
+        assert_eq!(next.dst_access, vk::AccessFlags::SHADER_WRITE);
+    }
+
+    #[test]
+    fn acquired_image_waits_for_acquire() {
+        let mut state = SyncState::after(
+            vk::ImageLayout::UNDEFINED,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        );
+        let barrier = state.access(COLOR_WRITE).unwrap();
+        assert_eq!(
+            barrier.src_stages,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        );
+    }
+}
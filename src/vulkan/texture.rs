@@ -11,13 +11,12 @@ pub struct Texture {
 
 impl Texture {
     pub fn create(
-        file: &str,
+        file: &[u8],
         bvk: &mut BabyVulkan,
         fence: vk::Fence,
         cmd_buf: vk::CommandBuffer,
     ) -> Option<Self> {
         //  Load the Image
-        let file = std::fs::read(file).unwrap();
         let mut x: i32 = 0;
         let mut y: i32 = 0;
         let mut comp: i32 = 0;
@@ -33,19 +32,46 @@ impl Texture {
             );
         }
 
-        //  Copy Image Data -> Staging Buffer
-        let image_size = (x * y * 4) as usize;
-        let mut staging_image = Buffer::create(
-            image_size,
+        if image.is_null() {
+            println!("[Texture] Could not decode the image");
+            None?;
+        }
+        let pixels = unsafe { std::slice::from_raw_parts(image, (x * y * 4) as usize) };
+        let texture = Self::create_from_pixels(
+            pixels,
+            vk::Extent2D {
+                width: x as u32,
+                height: y as u32,
+            },
+            vk::Format::R8G8B8A8_SRGB,
             bvk,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-        )?;
-        staging_image.map_copy_data(bvk, image, image_size);
+            fence,
+            cmd_buf,
+        );
 
         //  Cleanup the Image
         unsafe {
             stbi_image_free(image);
         }
+        texture
+    }
+
+    //  `pixels` are tightly packed rows of `format`.
+    pub fn create_from_pixels(
+        pixels: &[u8],
+        extent: vk::Extent2D,
+        format: vk::Format,
+        bvk: &mut BabyVulkan,
+        fence: vk::Fence,
+        cmd_buf: vk::CommandBuffer,
+    ) -> Option<Self> {
+        //  Copy Image Data -> Staging Buffer
+        let mut staging_image = Buffer::create(
+            pixels.len(),
+            bvk,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+        staging_image.map_copy_data(bvk, pixels.as_ptr(), pixels.len());
 
         //  Start Recording on the Command Buffer
         let cmd_begin_info = vk::CommandBufferBeginInfo::builder()
@@ -55,13 +81,13 @@ impl Texture {
 
         //  Create Image and Transfer `UNDEFINED` -> `TRANSFER_DST_OPTIMAL`
         let image_extent = vk::Extent3D {
-            width: x as u32,
-            height: y as u32,
+            width: extent.width,
+            height: extent.height,
             depth: 1,
         };
         let image = Image::create(
             bvk,
-            vk::Format::R8G8B8A8_SRGB,
+            format,
             vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
             image_extent,
         )?;
@@ -137,11 +163,6 @@ impl Texture {
             );
         }
 
-        //  Run Our Commands!
-        let submit_info = vk::SubmitInfo::builder()
-            .command_buffers(&[cmd_buf])
-            .build();
-
         //  Create a Texture Sampler
         let sampler_info = vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
@@ -161,11 +182,8 @@ impl Texture {
         //  Cleanup the Mess
         unsafe {
             bvk.dev.end_command_buffer(cmd_buf).ok()?;
-            bvk.dev
-                .queue_submit(bvk.transfer_queue, &[submit_info], fence)
-                .ok()?;
-            bvk.dev.wait_for_fences(&[fence], true, u64::MAX).ok()?;
-            bvk.dev.reset_fences(&[fence]).ok()?;
+            //  Run Our Commands!
+            bvk.submit_and_wait(bvk.transfer_queue, cmd_buf, fence)?;
             bvk.dev
                 .reset_command_buffer(cmd_buf, vk::CommandBufferResetFlags::empty())
                 .ok()?;
@@ -179,6 +197,111 @@ impl Texture {
         })
     }
 
+    //  An empty texture for compute shaders to write into.
+    //  It starts out `UNDEFINED`, so it has to be written (see `PassBuilder::write_storage_image`)
+    //  before it is sampled.
+    pub fn create_storage(bvk: &BabyVulkan, extent: vk::Extent2D) -> Option<Self> {
+        //  Storage images can't be sRGB
+        Self::create_empty(
+            bvk,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            extent,
+            vk::SamplerAddressMode::REPEAT,
+        )
+    }
+
+    //  An empty texture to render into, e.g. by importing it into a `RenderGraph`.
+    pub fn create_attachment(
+        bvk: &BabyVulkan,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Option<Self> {
+        //  Sampling past the edges (e.g. for FXAA) shouldn't wrap around to the other side
+        Self::create_empty(
+            bvk,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            extent,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        )
+    }
+
+    //  Depth to render into, then compare against when sampling, see `ShadowMap`.
+    //  Anything outside of it counts as lit.
+    pub fn create_shadow_map(
+        bvk: &BabyVulkan,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Option<Self> {
+        let image = Image::create(
+            bvk,
+            format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        )?;
+        //  Linear filtering compares the 4 nearest texels and blends the results
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe { bvk.dev.create_sampler(&sampler_info, None) }.ok()?;
+        let image_view =
+            bvk.create_image_view(image.image, image.format, vk::ImageAspectFlags::DEPTH)?;
+        Some(Texture {
+            image,
+            image_view,
+            sampler,
+        })
+    }
+
+    fn create_empty(
+        bvk: &BabyVulkan,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        extent: vk::Extent2D,
+        address_mode: vk::SamplerAddressMode,
+    ) -> Option<Self> {
+        let image = Image::create(
+            bvk,
+            format,
+            usage,
+            vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        )?;
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode)
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe { bvk.dev.create_sampler(&sampler_info, None) }.ok()?;
+        let image_view =
+            bvk.create_image_view(image.image, image.format, vk::ImageAspectFlags::COLOR)?;
+        Some(Texture {
+            image,
+            image_view,
+            sampler,
+        })
+    }
+
     pub fn destroy(&mut self, bvk: &BabyVulkan) {
         unsafe {
             bvk.dev.destroy_sampler(self.sampler, None);
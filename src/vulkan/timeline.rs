@@ -0,0 +1,140 @@
+use super::*;
+use std::cell::Cell;
+
+//  How long the CPU waits on the GPU before saying so, and how many times before giving up.
+//  A frame never takes this long, so something has gone very wrong by then.
+const WAIT_TIMEOUT_NS: u64 = 1_000_000_000;
+const WAIT_ATTEMPTS: u32 = 5;
+
+//  Calls `wait` with a timeout until it's done, complaining every time it isn't.
+//  Gives up on a lost device, any other error, or after `WAIT_ATTEMPTS`.
+pub fn wait_for_gpu(what: &str, mut wait: impl FnMut(u64) -> prelude::VkResult<()>) -> Option<()> {
+    for _ in 0..WAIT_ATTEMPTS {
+        match wait(WAIT_TIMEOUT_NS) {
+            Ok(()) => return Some(()),
+            Err(vk::Result::TIMEOUT) => println!("[Sync] Still waiting for {}", what),
+            Err(vk::Result::ERROR_DEVICE_LOST) => {
+                println!("[Sync] Lost the device while waiting for {}", what);
+                return None;
+            }
+            Err(e) => {
+                println!("[Sync] Waiting for {} failed: {}", what, e);
+                return None;
+            }
+        }
+    }
+    println!("[Sync] Gave up waiting for {}", what);
+    None
+}
+
+//  A timeline semaphore that every submission signals with the next value, so that waiting for
+//  any one of them is just waiting for its value. Needs Vulkan 1.2, see `BabyVulkan::timeline`.
+pub struct Timeline {
+    pub semaphore: vk::Semaphore,
+    //  The last value handed out
+    last: Cell<u64>,
+}
+
+impl Timeline {
+    pub fn create(dev: &Device) -> Option<Self> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0)
+            .build();
+        let semaphore_info = vk::SemaphoreCreateInfo::builder()
+            .push_next(&mut type_info)
+            .build();
+        let semaphore = unsafe { dev.create_semaphore(&semaphore_info, None) }.ok()?;
+        Some(Timeline {
+            semaphore,
+            last: Cell::new(0),
+        })
+    }
+
+    //  For the next submission to signal. Submissions have to go out in this order.
+    pub fn next(&self) -> u64 {
+        self.last.set(self.last.get() + 1);
+        self.last.get()
+    }
+
+    pub fn wait(&self, dev: &Device, value: u64) -> Option<()> {
+        let semaphores = [self.semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values)
+            .build();
+        wait_for_gpu("the timeline", |timeout| unsafe {
+            dev.wait_semaphores(&wait_info, timeout)
+        })
+    }
+
+    pub fn destroy(&self, dev: &Device) {
+        unsafe { dev.destroy_semaphore(self.semaphore, None) };
+    }
+}
+
+//  What the CPU waits on to know that a submission is done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuWait {
+    Fence(vk::Fence),
+    //  A value of `BabyVulkan::timeline`
+    Timeline(u64),
+}
+
+impl GpuWait {
+    //  For a submission about to go out. With a timeline that's the next value and `fence` is
+    //  left alone, otherwise `fence` is reset so that it can be signaled again.
+    pub fn next(bvk: &BabyVulkan, fence: vk::Fence) -> Option<Self> {
+        match &bvk.timeline {
+            Some(timeline) => Some(GpuWait::Timeline(timeline.next())),
+            None => {
+                unsafe { bvk.dev.reset_fences(&[fence]) }.ok()?;
+                Some(GpuWait::Fence(fence))
+            }
+        }
+    }
+
+    pub fn wait(self, bvk: &BabyVulkan) -> Option<()> {
+        match (self, &bvk.timeline) {
+            (GpuWait::Timeline(value), Some(timeline)) => timeline.wait(&bvk.dev, value),
+            (GpuWait::Fence(fence), _) => wait_for_gpu("a fence", |timeout| unsafe {
+                bvk.dev.wait_for_fences(&[fence], true, timeout)
+            }),
+            (GpuWait::Timeline(_), None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_give_up() {
+        let mut calls = 0;
+        let done = wait_for_gpu("test", |_| {
+            calls += 1;
+            if calls < 3 {
+                Err(vk::Result::TIMEOUT)
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!((done, calls), (Some(()), 3));
+
+        let mut calls = 0;
+        let lost = wait_for_gpu("test", |_| {
+            calls += 1;
+            Err(vk::Result::ERROR_DEVICE_LOST)
+        });
+        assert_eq!((lost, calls), (None, 1));
+
+        let mut calls = 0;
+        let stuck = wait_for_gpu("test", |_| {
+            calls += 1;
+            Err(vk::Result::TIMEOUT)
+        });
+        assert_eq!((stuck, calls), (None, WAIT_ATTEMPTS));
+    }
+}
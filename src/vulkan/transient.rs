@@ -0,0 +1,157 @@
+use super::*;
+
+//  How much each frame can push. Uniforms are a few hundred bytes each, so this leaves plenty
+//  for immediate-mode vertices and indirect args.
+pub const TRANSIENT_FRAME_SIZE: usize = 256 * 1024;
+
+//  Data that only lives for one frame: uniforms, immediate-mode vertices, indirect args.
+//  One big buffer that stays mapped, split into a region per frame in flight. Each frame pushes
+//  into its region one after another, and starts over once its last submission is done, see
+//  `reset`.
+pub struct TransientBuffer {
+    pub buffer: Buffer,
+    mapped: *mut u8,
+    frame_size: usize,
+    //  Per frame, where the next push goes
+    heads: Vec<usize>,
+    uniform_alignment: usize,
+}
+
+impl TransientBuffer {
+    pub fn create(bvk: &BabyVulkan, frame_size: usize, frame_count: usize) -> Option<Self> {
+        let uniform_alignment = bvk
+            .gpu_properties
+            .limits
+            .min_uniform_buffer_offset_alignment as usize;
+        //  So that every region starts out aligned
+        let frame_size = align_up(frame_size, uniform_alignment);
+        let mut buffer = Buffer::create(
+            frame_size * frame_count,
+            bvk,
+            vk::BufferUsageFlags::UNIFORM_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::INDEX_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER,
+        )?;
+        let mapped = unsafe { bvk.alloc.map_memory(&mut buffer.allocation) }.ok()?;
+        Some(TransientBuffer {
+            buffer,
+            mapped,
+            frame_size,
+            heads: vec![0; frame_count],
+            uniform_alignment,
+        })
+    }
+
+    //  `frame`'s last submission is done, so everything it pushed can be written over.
+    pub fn reset(&mut self, frame: usize) {
+        self.heads[frame] = 0;
+    }
+
+    //  Copy `data` into `frame`'s region, at a multiple of `alignment` (a power of two).
+    //  Vertices and indices only need their own alignment, indirect args 4.
+    //  Returns where in `buffer` it went.
+    pub fn push<T: Copy>(&mut self, frame: usize, data: &[T], alignment: usize) -> Option<u64> {
+        let size = std::mem::size_of_val(data);
+        let alignment = alignment.max(std::mem::align_of::<T>());
+        let (start, end) =
+            bump(self.heads[frame], size, alignment, self.frame_size).or_else(|| {
+                println!(
+                    "[Transient] Frame {} is out of space for {} more bytes",
+                    frame, size
+                );
+                None
+            })?;
+        self.heads[frame] = end;
+        let offset = frame * self.frame_size + start;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.mapped.add(offset), size)
+        };
+        Some(offset as u64)
+    }
+
+    //  At an offset a uniform buffer binding can start at.
+    //  Returns the dynamic offset for a binding that starts at the beginning of `buffer`.
+    pub fn push_uniform<T: Copy>(&mut self, frame: usize, data: &T) -> Option<u32> {
+        let offset = self.push(frame, std::slice::from_ref(data), self.uniform_alignment)?;
+        Some(offset as u32)
+    }
+
+    pub fn destroy(&mut self, bvk: &mut BabyVulkan) {
+        unsafe { bvk.alloc.unmap_memory(&mut self.buffer.allocation) };
+        self.buffer.destroy(bvk);
+    }
+}
+
+//  Binding a transient uniform takes a dynamic offset, so every uniform buffer in `bindings`
+//  becomes a dynamic one.
+pub fn dynamic_bindings(
+    bindings: &[vk::DescriptorSetLayoutBinding],
+) -> Vec<vk::DescriptorSetLayoutBinding> {
+    bindings
+        .iter()
+        .map(|binding| {
+            let mut binding = *binding;
+            if binding.descriptor_type == vk::DescriptorType::UNIFORM_BUFFER {
+                binding.descriptor_type = vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC;
+            }
+            binding
+        })
+        .collect()
+}
+
+//  `alignment` has to be a power of two, which Vulkan promises for its limits.
+pub fn align_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+//  Where `size` bytes go after `head`, and the head after them, if they still fit.
+fn bump(head: usize, size: usize, alignment: usize, capacity: usize) -> Option<(usize, usize)> {
+    let start = align_up(head, alignment);
+    let end = start.checked_add(size)?;
+    (end <= capacity).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bumps_aligned_until_full() {
+        assert_eq!(align_up(0, 256), 0);
+        assert_eq!(align_up(1, 256), 256);
+        assert_eq!(align_up(256, 256), 256);
+
+        let (start, head) = bump(0, 100, 256, 1024).unwrap();
+        assert_eq!((start, head), (0, 100));
+        let (start, head) = bump(head, 100, 256, 1024).unwrap();
+        assert_eq!((start, head), (256, 356));
+        let (start, head) = bump(head, 12, 4, 1024).unwrap();
+        assert_eq!((start, head), (356, 368));
+        assert_eq!(bump(head, 1024 - 512 + 1, 512, 1024), None);
+        assert_eq!(bump(head, 1024 - 512, 512, 1024), Some((512, 1024)));
+    }
+
+    #[test]
+    fn uniform_buffers_become_dynamic() {
+        let binding = |binding, ty| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(ty)
+                .descriptor_count(1)
+                .build()
+        };
+        let bindings = dynamic_bindings(&[
+            binding(0, vk::DescriptorType::UNIFORM_BUFFER),
+            binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+        ]);
+        assert_eq!(
+            bindings[0].descriptor_type,
+            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+        );
+        assert_eq!(
+            bindings[1].descriptor_type,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        );
+    }
+}
@@ -1,55 +1,49 @@
 use super::*;
 
+//  `UniformData` in src/include/scene.glsl
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct UniformData {
     pub color: glm::Vec4,
+    pub light_view_proj: glm::Mat4,
+    //  Only xyz (or rgb) of these are used, w keeps std140 happy
+    pub light_dir: glm::Vec4,
+    pub light_color: glm::Vec4,
+    pub ambient_color: glm::Vec4,
+    pub camera_position: glm::Vec4,
+    //  x: Strength, y: Shininess
+    pub specular: glm::Vec4,
 }
 
-impl UniformData {
-    pub fn binding() -> vk::DescriptorSetLayoutBinding {
-        vk::DescriptorSetLayoutBinding::builder()
-            .binding(0)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .immutable_samplers(&[])
-            //  Note, putting `descriptor_count` in front may cause it to get overritten. Maybe
-            //  this is a bug with ash, or it's intentional.
-            .descriptor_count(1)
-            .build()
-    }
-}
-
-pub struct UniformTexture {}
-
-impl UniformTexture {
-    pub fn binding() -> vk::DescriptorSetLayoutBinding {
-        let mut r = vk::DescriptorSetLayoutBinding::builder()
-            .binding(1)
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .immutable_samplers(&[])
-            .descriptor_count(1)
-            .build();
-
-        //  ???
-        r.p_immutable_samplers = std::ptr::null();
-
-        r
-    }
-}
-
-pub struct Uniform<const N: usize> {
+//  The scene's descriptor set, shared by every frame in flight. The uniform buffer binding is
+//  dynamic: each frame pushes its `UniformData` into the transient buffer and says where when
+//  binding the set, so that a frame never writes what an earlier one is still reading.
+pub struct Uniform {
     pub descriptor_set_layout: vk::DescriptorSetLayout,
-    pub uniform_bufs: [Buffer; N],
     pub descriptor_pool: vk::DescriptorPool,
-    pub descriptor_sets: [vk::DescriptorSet; N],
+    pub descriptor_set: vk::DescriptorSet,
+    //  Kept to fill in a new set, see `swap_texture`
+    bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    image_infos: Vec<(u32, vk::DescriptorImageInfo)>,
+    uniform_buf: vk::Buffer,
 }
 
-impl<const N: usize> Uniform<N> {
-    pub fn create(bvk: &BabyVulkan, texture: &Texture) -> Option<Self> {
+impl Uniform {
+    //  `bindings` should come from `ShaderInterface::reflect` so that we never disagree with the
+    //  shaders.
+    //  `textures` says which texture goes into which sampler binding, `uniform_buf` is
+    //  `TransientBuffer::buffer`.
+    pub fn create(
+        bvk: &BabyVulkan,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+        textures: &[(u32, &Texture)],
+        uniform_buf: vk::Buffer,
+    ) -> Option<Self> {
+        let bindings = dynamic_bindings(bindings);
+
         //  Create Descriptor Set Layout
         let descriptor_set_layout = vk::DescriptorSetLayoutCreateInfo::builder()
-            .bindings(&[UniformData::binding(), UniformTexture::binding()])
+            .bindings(&bindings)
             .build();
 
         let descriptor_set_layout = unsafe {
@@ -58,37 +52,79 @@ impl<const N: usize> Uniform<N> {
         }
         .ok()?;
 
-        //  Create Uniform Data Buffers
-        let mut uniform_bufs = [const { None }; N];
-        for b in uniform_bufs.iter_mut() {
-            *b = Some(Buffer::create(
-                align_uniform_buffer_size(bvk, std::mem::size_of::<UniformData>()),
-                bvk,
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-            )?);
+        let image_infos: Vec<(u32, vk::DescriptorImageInfo)> = textures
+            .iter()
+            .map(|(binding, texture)| {
+                (
+                    *binding,
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .image_view(texture.image_view)
+                        .sampler(texture.sampler)
+                        .build(),
+                )
+            })
+            .collect();
+
+        let mut uniform = Uniform {
+            descriptor_set_layout,
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set: vk::DescriptorSet::null(),
+            bindings,
+            image_infos,
+            uniform_buf,
+        };
+        (uniform.descriptor_pool, uniform.descriptor_set) =
+            uniform.create_set(bvk, &uniform.image_infos)?;
+        Some(uniform)
+    }
+
+    //  Point every binding that had `old` at `new` instead.
+    //  Sets in flight can't be written to, so this fills in a new one. Returns the old pool,
+    //  for `Frames::retire`.
+    pub fn swap_texture(
+        &mut self,
+        bvk: &BabyVulkan,
+        old: &Texture,
+        new: &Texture,
+    ) -> Option<vk::DescriptorPool> {
+        let mut image_infos = self.image_infos.clone();
+        for (_, image_info) in &mut image_infos {
+            if image_info.image_view == old.image_view {
+                image_info.image_view = new.image_view;
+                image_info.sampler = new.sampler;
+            }
         }
-        let uniform_bufs: [Buffer; N] = uniform_bufs
-            .into_iter()
-            .map(|buffer| buffer.unwrap())
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+        let (descriptor_pool, descriptor_set) = self.create_set(bvk, &image_infos)?;
+        self.image_infos = image_infos;
+        self.descriptor_set = descriptor_set;
+        Some(std::mem::replace(
+            &mut self.descriptor_pool,
+            descriptor_pool,
+        ))
+    }
 
+    //  A pool with just the one set, filled in.
+    fn create_set(
+        &self,
+        bvk: &BabyVulkan,
+        image_infos: &[(u32, vk::DescriptorImageInfo)],
+    ) -> Option<(vk::DescriptorPool, vk::DescriptorSet)> {
         //  Create Descriptor Pools
-        let descriptor_pool_sizes = [
-            vk::DescriptorPoolSize::builder()
-                .descriptor_count(N as u32)
-                .ty(vk::DescriptorType::UNIFORM_BUFFER)
-                .build(),
-            vk::DescriptorPoolSize::builder()
-                .descriptor_count(N as u32)
-                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .build(),
-        ];
+        let descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = self
+            .bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorPoolSize::builder()
+                    .descriptor_count(binding.descriptor_count)
+                    .ty(binding.descriptor_type)
+                    .build()
+            })
+            .collect();
 
         let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
             .pool_sizes(&descriptor_pool_sizes)
-            .max_sets(N as u32)
+            .max_sets(1)
             .build();
 
         let descriptor_pool =
@@ -96,86 +132,71 @@ impl<const N: usize> Uniform<N> {
 
         //  Create Descriptor Sets
         let descriptor_sets_info = vk::DescriptorSetAllocateInfo::builder()
-            .set_layouts(&[descriptor_set_layout; N])
+            .set_layouts(std::slice::from_ref(&self.descriptor_set_layout))
             .descriptor_pool(descriptor_pool)
             .build();
 
-        let descriptor_sets: [vk::DescriptorSet; N] =
-            unsafe { bvk.dev.allocate_descriptor_sets(&descriptor_sets_info) }
-                .ok()?
-                .into_iter()
-                .collect::<Vec<vk::DescriptorSet>>()
-                .try_into()
-                .ok()?;
+        let descriptor_set =
+            unsafe { bvk.dev.allocate_descriptor_sets(&descriptor_sets_info) }.ok()?[0];
 
         //  Configure Descriptor Sets
-        descriptor_sets
+        //  Every binding the shaders declare gets filled with whatever we have of that type.
+        //  UniformData
+        let buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buf)
+            .range(std::mem::size_of::<UniformData>() as u64)
+            .offset(0)
+            .build();
+
+        let descriptor_writes = self
+            .bindings
             .iter()
-            .zip(uniform_bufs.iter())
-            .for_each(|(&set, uniform_buf)| {
-                //  UniformData
-                let buffer_info = vk::DescriptorBufferInfo::builder()
-                    .buffer(uniform_buf.buf)
-                    .range(std::mem::size_of::<UniformData>() as u64)
-                    .offset(0)
-                    .build();
-                let buf_descriptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(set)
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[buffer_info])
-                    .build();
-
-                //  UniformTexture
-                let image_info = vk::DescriptorImageInfo::builder()
-                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                    .image_view(texture.image_view)
-                    .sampler(texture.sampler)
-                    .build();
-                let image_descriptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(set)
-                    .dst_binding(1)
+            .map(|binding| {
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(binding.binding)
                     .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[image_info])
-                    .build();
-
-                unsafe {
-                    bvk.dev.update_descriptor_sets(
-                        &[buf_descriptor_write, image_descriptor_write],
-                        &[],
-                    )
+                    .descriptor_type(binding.descriptor_type);
+                match binding.descriptor_type {
+                    vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => Some(
+                        write
+                            .buffer_info(std::slice::from_ref(&buffer_info))
+                            .build(),
+                    ),
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER => image_infos
+                        .iter()
+                        .find(|(texture_binding, _)| *texture_binding == binding.binding)
+                        .map(|(_, image_info)| {
+                            write.image_info(std::slice::from_ref(image_info)).build()
+                        })
+                        .or_else(|| {
+                            println!(
+                                "[Uniform] No texture was given for binding {}",
+                                binding.binding
+                            );
+                            None
+                        }),
+                    ty => {
+                        println!(
+                            "[Uniform] Don't know what to put in binding {} ({:?})",
+                            binding.binding, ty
+                        );
+                        None
+                    }
                 }
-            });
+            })
+            .collect::<Option<Vec<_>>>()?;
 
-        Some(Uniform {
-            descriptor_set_layout,
-            uniform_bufs,
-            descriptor_pool,
-            descriptor_sets,
-        })
+        unsafe { bvk.dev.update_descriptor_sets(&descriptor_writes, &[]) }
+
+        Some((descriptor_pool, descriptor_set))
     }
 
-    pub fn destroy(&mut self, bvk: &mut BabyVulkan) {
+    pub fn destroy(&mut self, bvk: &BabyVulkan) {
         unsafe {
-            self.uniform_bufs
-                .iter_mut()
-                .for_each(|buf| buf.destroy(bvk));
+            bvk.dev.destroy_descriptor_pool(self.descriptor_pool, None);
             bvk.dev
                 .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
-            bvk.dev.destroy_descriptor_pool(self.descriptor_pool, None);
         }
     }
 }
-
-//  Each GPU has a unique memory alignment requirement
-fn align_uniform_buffer_size(bvk: &BabyVulkan, size: usize) -> usize {
-    let props = unsafe { bvk.instance.get_physical_device_properties(bvk.gpu) };
-    let min_ubo_alignment = props.limits.min_uniform_buffer_offset_alignment as usize;
-    if size > 0 {
-        (size + min_ubo_alignment - 1) & !(min_ubo_alignment - 1)
-    } else {
-        size
-    }
-}